@@ -0,0 +1,42 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shade::lexer::Lexer;
+
+/// A synthetic library of `n` functions mixing all the kinds of tokens.
+fn library(n: usize) -> String {
+    let mut src = String::new();
+    for i in 0..n {
+        src.push_str(&format!(
+            "--| Signed distance to circle {i}.\n\
+             circle_{i} = (c: vec2f) (r: f32) (p: vec2f) :\n    \
+             -{{ the distance to the center\n       minus the radius }}-\n    \
+             (length (p - c) - r) |> max 0.5f32 |> min 1_000.0\n\
+             color_{i} = \"circle {{i}}: \\u{{2713}}\" |> print 0xff_u8 '\\n'\n\
+             é_{i} = λ_{i} * 2\n\n"
+        ));
+    }
+    src
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for n in [1_000, 10_000] {
+        let src = library(n);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(BenchmarkId::new("plain", n), &src, |b, src| {
+            b.iter(|| Lexer::new(src).lex().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("lossless_layout", n), &src, |b, src| {
+            b.iter(|| Lexer::new(src).lossless().with_layout().lex().unwrap())
+        });
+    }
+    group.finish();
+
+    let src = library(10_000);
+    let lexed = Lexer::new(&src).lex().unwrap();
+    let mid = src.len() / 2;
+    let at = mid + src[mid..].find("circle").unwrap();
+    c.bench_function("relex/rename", |b| b.iter(|| lexed.relex(at..at + 6, "disk").unwrap()));
+}
+
+criterion_group!(benches, bench_lexer);
+criterion_main!(benches);
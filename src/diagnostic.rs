@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use crate::{
+    lines::Encoding,
+    source_map::SourceMap,
+    utils::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A span of source with a message, the primary labels point at the problem and the
+/// secondary ones give context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A message about the source shared by all the stages of the compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+/// How a diagnostic is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Plain,
+    /// Plain with ANSI colors for terminals.
+    Color,
+    Json,
+}
+
+/// Errors turned into diagnostics.
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn render(&self, map: &SourceMap, mode: RenderMode) -> String {
+        match mode {
+            RenderMode::Plain => self.render_text(map, Style { color: false }),
+            RenderMode::Color => self.render_text(map, Style { color: true }),
+            RenderMode::Json => self.to_json(map).to_string(),
+        }
+    }
+
+    fn render_text(&self, map: &SourceMap, style: Style) -> String {
+        let mut out = String::new();
+        let (severity, color) = match self.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+            Severity::Note => ("note", GREEN),
+        };
+        let code = self.code.map(|c| format!("[{c}]")).unwrap_or_default();
+        let header = style.paint(color, &format!("{severity}{code}"));
+        writeln!(out, "{header}{}", style.paint(BOLD, &format!(": {}", self.message))).unwrap();
+
+        // The labels grouped by file and line, the file of the first primary label first.
+        let main_file = self.labels.iter().find(|l| l.primary).or(self.labels.first()).map(|l| l.span.file);
+        let mut lines = BTreeMap::<_, Vec<_>>::new();
+        for label in &self.labels {
+            let start = map.line_col(label.span, Encoding::Utf32);
+            let key = (Some(label.span.file) != main_file, label.span.file, start.line);
+            lines.entry(key).or_default().push((start, label));
+        }
+        let width = lines.keys().map(|(_, _, line)| line.to_string().len()).max().unwrap_or(0);
+        let pad = " ".repeat(width);
+        let gutter = style.paint(BLUE, &format!("{pad} |"));
+
+        let mut file = None;
+        for ((_, id, line), mut labels) in lines {
+            if file != Some(id) {
+                let in_file = || self.labels.iter().filter(|l| l.span.file == id);
+                let label = in_file().find(|l| l.primary).or(in_file().next()).unwrap();
+                let start = map.line_col(label.span, Encoding::Utf32);
+                let path = map.path(label.span).display();
+                let arrow = style.paint(BLUE, &format!("{pad}-->"));
+                writeln!(out, "{arrow} {path}:{}:{}", start.line, start.col).unwrap();
+                writeln!(out, "{gutter}").unwrap();
+                file = Some(id);
+            }
+            let text = map.file(id).lines().line_text(line);
+            let number = style.paint(BLUE, &format!("{line:>width$} |"));
+            writeln!(out, "{number} {text}").unwrap();
+            labels.sort_by_key(|(start, _)| start.col);
+            for (start, label) in labels {
+                let end = map.file(id).lines().line_col(label.span.end.pos, Encoding::Utf32);
+                let end_col = if end.line == line { end.col } else { text.chars().count() + 1 };
+                // Keep the tabs so the marks line up with the text.
+                let indent: String = text
+                    .chars()
+                    .take(start.col - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let (mark, color) = if label.primary { ('^', color) } else { ('-', BLUE) };
+                let marks = mark.to_string().repeat(end_col.saturating_sub(start.col).max(1));
+                let marks = style.paint(color, &format!("{marks} {}", label.message));
+                writeln!(out, "{gutter} {indent}{}", marks.trim_end()).unwrap();
+            }
+        }
+
+        let equals = style.paint(BLUE, &format!("{pad} ="));
+        for note in &self.notes {
+            writeln!(out, "{equals} {}: {note}", style.paint(BOLD, "note")).unwrap();
+        }
+        if let Some(help) = &self.help {
+            writeln!(out, "{equals} {}: {help}", style.paint(BOLD, "help")).unwrap();
+        }
+        out
+    }
+
+    fn to_json(&self, map: &SourceMap) -> Value {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        let position = |loc: Loc, file| {
+            let lines = map.file(file).lines();
+            let pos = lines.line_col(loc.pos, Encoding::Utf32);
+            json!({ "offset": loc.pos, "line": pos.line, "col": pos.col })
+        };
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|l| {
+                json!({
+                    "file": map.path(l.span).display().to_string(),
+                    "start": position(l.span.start, l.span.file),
+                    "end": position(l.span.end, l.span.file),
+                    "message": l.message,
+                    "primary": l.primary,
+                })
+            })
+            .collect();
+        json!({
+            "severity": severity,
+            "code": self.code,
+            "message": self.message,
+            "labels": labels,
+            "notes": self.notes,
+            "help": self.help,
+        })
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{code}{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut map = SourceMap::new();
+        let file = map.add("main.sd", "main =\n\tf = (a b)\n");
+        let lexed = map.lex(file).unwrap();
+        let spans = lexed.spans();
+        let diagnostic = Diagnostic::error("Mismatched delimiters.")
+            .with_code("L0002")
+            .with_label(spans[7], "closes nothing")
+            .with_secondary(spans[4], "opened here")
+            .with_note("Delimiters must be closed in order.")
+            .with_help("Replace `)` with `]`.");
+
+        let plain = diagnostic.render(&map, RenderMode::Plain);
+        assert_eq!(
+            plain,
+            "error[L0002]: Mismatched delimiters.\n\
+             \x20--> main.sd:2:10\n\
+             \x20 |\n\
+             2 | \tf = (a b)\n\
+             \x20 | \t    - opened here\n\
+             \x20 | \t        ^ closes nothing\n\
+             \x20 = note: Delimiters must be closed in order.\n\
+             \x20 = help: Replace `)` with `]`.\n"
+        );
+
+        let colored = diagnostic.render(&map, RenderMode::Color);
+        assert!(colored.contains("\x1b[1;31merror[L0002]\x1b[0m"));
+
+        let json: Value = serde_json::from_str(&diagnostic.render(&map, RenderMode::Json)).unwrap();
+        assert_eq!(json["code"], "L0002");
+        assert_eq!(json["labels"][0]["start"], json!({ "offset": 16, "line": 2, "col": 10 }));
+        assert_eq!(json["labels"][1]["primary"], false);
+    }
+
+    #[test]
+    fn test_lex_error() {
+        let mut map = SourceMap::new();
+        let file = map.add("lib.sd", "f = [a\n  b)");
+        let err = &map.lex(file).unwrap().minor_errors()[0];
+        let plain = err.to_diagnostic().render(&map, RenderMode::Plain);
+        assert_eq!(
+            plain,
+            "error[L0003]: Closing delimiter doesn't match the one opened.\n\
+             \x20--> lib.sd:2:4\n\
+             \x20 |\n\
+             1 | f = [a\n\
+             \x20 |     - opened here\n\
+             2 |   b)\n\
+             \x20 |    ^ doesn't match the opening delimiter\n"
+        );
+
+        let file = map.add("esc.sd", "s = \"a\\qb\"");
+        let err = &map.lex(file).unwrap().minor_errors()[0];
+        let plain = err.to_diagnostic().render(&map, RenderMode::Plain);
+        assert_eq!(
+            plain,
+            "error[L0010]: Unknown escape sequence.\n\
+             \x20--> esc.sd:1:7\n\
+             \x20 |\n\
+             1 | s = \"a\\qb\"\n\
+             \x20 |       ^^ unknown escape\n"
+        );
+
+        let file = map.add("open.sd", "f = (a\n  b c");
+        let err = &map.lex(file).unwrap().minor_errors()[0];
+        assert_eq!(
+            err.to_diagnostic().render(&map, RenderMode::Plain),
+            "error[L0004]: Delimiter is never closed.\n\
+             \x20--> open.sd:1:5\n\
+             \x20 |\n\
+             1 | f = (a\n\
+             \x20 |     ^ never closed\n"
+        );
+
+        let file = map.add("comment.sd", "a -{ b\n c");
+        let err = map.lex(file).unwrap_err();
+        assert_eq!(
+            err.0.to_diagnostic().render(&map, RenderMode::Plain),
+            "error[L0005]: Unexpected end of input in a block comment.\n\
+             \x20--> comment.sd:2:3\n\
+             \x20 |\n\
+             1 | a -{ b\n\
+             \x20 |   - comment opened here\n\
+             2 |  c\n\
+             \x20 |   ^ input ends here\n"
+        );
+    }
+}
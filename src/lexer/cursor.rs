@@ -0,0 +1,149 @@
+use crate::utils::*;
+
+/// The input of the lexer: a position in the source read byte by byte, ASCII chars are
+/// read without decoding UTF-8.
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor<'a> {
+    source: &'a str,
+    loc: Loc,
+}
+
+/// A position saved in a [`Cursor`].
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint(Loc);
+
+impl<'a> Cursor<'a> {
+    /// A cursor at `loc` in `source`.
+    pub fn new(source: &'a str, loc: Loc) -> Self {
+        Self { source, loc }
+    }
+
+    /// The location of the next char.
+    pub fn loc(&self) -> Loc {
+        self.loc
+    }
+
+    /// Save the position of the cursor, including its location, to look ahead.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.loc)
+    }
+
+    /// Go back to a position saved by [`Cursor::checkpoint`].
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.loc = checkpoint.0;
+    }
+
+    /// The text read since `start`.
+    pub fn since(&self, start: Loc) -> &'a str {
+        &self.source[start.pos..self.loc.pos]
+    }
+
+    /// The source not read yet.
+    pub fn rest(&self) -> &'a str {
+        &self.source[self.loc.pos..]
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        match *self.source.as_bytes().get(self.loc.pos)? {
+            b if b.is_ascii() => Some(b as char),
+            _ => self.rest().chars().next(),
+        }
+    }
+
+    /// The char `n` places ahead, `peek_nth(0)` being the next one.
+    pub fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    pub fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.loc.update(c);
+        Some(c)
+    }
+
+    /// Advance to the next char if it fulfills the predicate.
+    pub fn next_if(&mut self, pred: impl Fn(&char) -> bool) -> Option<char> {
+        let c = self.peek().filter(pred)?;
+        self.loc.update(c);
+        Some(c)
+    }
+
+    /// Advance to the next char if it is `c`.
+    pub fn next_is(&mut self, c: char) -> bool {
+        self.next_if(|x| *x == c).is_some()
+    }
+
+    /// Skip the chars while they fulfill the predicate.
+    pub fn skip_while(&mut self, pred: impl Fn(&char) -> bool) {
+        while self.next_if(&pred).is_some() {}
+    }
+
+    /// Skip the ASCII bytes fulfilling `pred` and return them, the bytes can't be new lines.
+    pub fn eat_ascii(&mut self, pred: impl Fn(u8) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.bytes().position(|b| !(b.is_ascii() && pred(b))).unwrap_or(rest.len());
+        debug_assert!(!rest[..len].contains('\n'));
+        self.loc.pos += len;
+        self.loc.col += len;
+        &rest[..len]
+    }
+
+    /// Skip to the next new line or to the end of the input and return the skipped text.
+    pub fn eat_line(&mut self) -> &'a str {
+        let rest = self.rest();
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        self.loc.pos += line.len();
+        self.loc.col += if line.is_ascii() { line.len() } else { line.chars().count() };
+        line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_cursor() {
+        let mut cursor = Cursor::new("ab\nçd -- e\nf", Loc::default());
+        assert_eq!(cursor.peek_nth(3), Some('ç'));
+        assert_eq!(cursor.eat_ascii(|b| b.is_ascii_alphabetic()), "ab");
+        assert!(cursor.next_is('\n'));
+        let checkpoint = cursor.checkpoint();
+        assert_eq!(cursor.next(), Some('ç'));
+        assert_eq!(cursor.loc(), Loc { pos: 5, row: 1, col: 1 });
+        assert_eq!(cursor.eat_line(), "d -- e");
+        assert_eq!(cursor.loc(), Loc { pos: 11, row: 1, col: 7 });
+        cursor.rewind(checkpoint);
+        assert_eq!(cursor.loc(), Loc { pos: 3, row: 1, col: 0 });
+        assert_eq!(cursor.rest(), "çd -- e\nf");
+        assert_eq!(cursor.eat_line(), "çd -- e");
+        cursor.skip_while(|_| true);
+        assert_eq!(cursor.loc(), Loc { pos: 13, row: 2, col: 1 });
+        assert_eq!(cursor.next(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_fast_paths_track_locs(source in "[a-c_ \n\t\u{e9}\u{3bb}\u{1f600}-]{0,40}") {
+            let mut cursor = Cursor::new(&source, Loc::default());
+            loop {
+                let start = cursor.loc();
+                match cursor.peek() {
+                    None => break,
+                    Some('-') => {
+                        cursor.eat_line();
+                    }
+                    Some(c) if c.is_ascii() && c != '\n' => {
+                        cursor.eat_ascii(|b| b != b'\n');
+                    }
+                    Some(_) => {
+                        cursor.next();
+                    }
+                }
+                prop_assert_eq!(cursor.loc(), start.advanced(cursor.since(start)));
+            }
+            prop_assert_eq!(cursor.loc(), Loc::default().advanced(&source));
+        }
+    }
+}
@@ -1,226 +1,938 @@
 use std::vec;
 
-use crate::{utils::*, input::Input};
+use crate::{utils::*, lines::LineIndex, diagnostic::{Diagnostic, ToDiagnostic}, symbol::Symbol};
 
 // TODO: Arrays
 
 pub mod token;
+mod cursor;
+mod string;
+mod relex;
 use thiserror::Error;
 use token::*;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use unicode_security::{skeleton, MixedScript};
+use string::*;
+use cursor::Cursor;
 
 pub type TkHandle = usize;
 
 use Reason::*;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Clone, PartialEq)]
 pub enum Reason {
     #[error("Unexpected end of input.")]
     UnexpectedEOI,
-    #[error("Unbalanced delims.")]
+    // The locations are shown by the labels of the diagnostics.
+    #[error("Closing delimiter was never opened.")]
     UnbalancedDelimiter(Loc),
+    #[error("Closing delimiter doesn't match the one opened.")]
+    MismatchedDelimiter(Loc, Loc),
+    #[error("Delimiter is never closed.")]
+    UnclosedDelimiter(Loc),
+    #[error("Unexpected end of input in a block comment.")]
+    UnterminatedComment(Loc),
+    #[error("Integer literal out of range.")]
+    IntOutOfRange,
+    #[error("Expected digits after the base prefix.")]
+    MissingDigits,
+    #[error("Malformed float literal.")]
+    MalformedFloat,
+    #[error("Invalid suffix, expected a scalar type such as `u32` or `f16`.")]
+    InvalidSuffix,
+    #[error("Unknown escape sequence.")]
+    UnknownEscape(Loc),
+    #[error("Invalid unicode escape, expected `\\u{{X}}` with 1 to 6 hex digits.")]
+    InvalidUnicodeEscape(Loc),
+    #[error("Empty char litteral.")]
+    EmptyChar,
+    #[error("Unterminated char litteral.")]
+    UnterminatedChar,
+    #[error("Char litteral holds more than one codepoint.")]
+    MultiCharLiteral,
+    #[error("Dedent to a column matching no enclosing block.")]
+    InconsistentDedent,
+    #[error("Tab in the indentation of a line, indent with spaces.")]
+    TabIndent,
+    #[error("Empty interpolation, write `\\{{` and `\\}}` for braces.")]
+    EmptyInterpolation,
+    #[error("Digit separator `_` not followed by a digit.")]
+    TrailingSeparator,
+    #[error("Float literal out of range.")]
+    FloatOutOfRange,
+    #[error("Digit not in the base of the integer literal.")]
+    InvalidDigit,
+    #[error("Identifier mixing several scripts.")]
+    MixedScriptIdent,
+    #[error("Identifier that can be confused with `{_0}`.")]
+    ConfusableIdent(IStr),
 }
-type Result<T> = std::result::Result<T, (LexError, LexedBuffer)>;
-
-pub type LexerInput<'a> = Input<std::str::Chars<'a>, Loc>;
+pub(crate) type Result<T> = std::result::Result<T, Box<(LexError, LexedBuffer)>>;
+/// The result of a lexing step, the state is turned into a partial buffer on errors.
+type Step = std::result::Result<(), LexError>;
 
 #[derive(Debug)]
 pub enum LexMode {
     Default,
+    /// Lexing an expression embedded in a string.
+    Interpolation(StringState),
 }
 
-#[derive(Debug, Error)]
-#[error("Lexing error at: {} because {}", loc, reason)]
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("Lexing error at: {} because {}", span.start, reason)]
 pub struct LexError {
-    loc: Loc,
+    span: Span,
     reason: Reason,
+    /// The token being lexed when the error was found.
+    token: TkHandle,
 }
 
-fn lex(input: &str) -> Result<LexedBuffer> {
-    let mut state = LexerState::new(input);
-    state.trim_whitespaces();
-    while let Some(c) = state.input.next() {
-        state = match c {
-            ':' => state.push_token(Colon),
-            '=' => state.push_token(Equals),
-            '(' => state.push_token(Lpar),
-            ')' => state.push_token(Rpar),
-            '{' => state.push_token(Lbrace),
-            '}' => state.push_token(Rbrace),
-            '[' => state.push_token(Lbracket),
-            ']' => state.push_token(Rbracket),
-            '|' => state.push_token(Bar),
-            '@' => state.push_token(At),
-            '$' => state.push_token(Dollar),
-            '<' => state.push_token(LT),
-            '>' => state.push_token(GT),
-            '.' => state.push_token(Dot),
-            ',' => state.push_token(Comma),
-            '"' => lex_string(state)?,
-            '-' => resolve_comment(state)?,
-            '_' => lex_identifier(state)?,
-            _ if c.is_alphabetic() || c == '_' => lex_ident_or_keyword(state, c)?,
-            '0' if state.input.next_is('b') => lex_bin_int(state)?,
-            '0' if state.input.next_is('x') => lex_hex_int(state)?,
-            _ if c.is_numeric() => lex_float_or_int(state)?,
-            _ => state.push_token(Unrecognized),
+impl LexError {
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+}
+
+impl Reason {
+    /// Whether the reason is only a warning, the token is still valid.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, MixedScriptIdent | ConfusableIdent(_))
+    }
+
+    /// Apply `f` to the locations held by the reason.
+    fn map_locs(self, f: impl Fn(Loc) -> Loc) -> Self {
+        match self {
+            UnbalancedDelimiter(close) => UnbalancedDelimiter(f(close)),
+            MismatchedDelimiter(open, close) => MismatchedDelimiter(f(open), f(close)),
+            UnclosedDelimiter(open) => UnclosedDelimiter(f(open)),
+            UnterminatedComment(open) => UnterminatedComment(f(open)),
+            UnknownEscape(loc) => UnknownEscape(f(loc)),
+            InvalidUnicodeEscape(loc) => InvalidUnicodeEscape(f(loc)),
+            r => r,
+        }
+    }
+}
+
+impl ToDiagnostic for LexError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let (code, label) = match &self.reason {
+            UnexpectedEOI => ("L0001", "input ends here"),
+            UnbalancedDelimiter(_) => ("L0002", "closes nothing"),
+            MismatchedDelimiter(..) => ("L0003", "doesn't match the opening delimiter"),
+            UnclosedDelimiter(_) => ("L0004", "never closed"),
+            UnterminatedComment(_) => ("L0005", "input ends here"),
+            IntOutOfRange => ("L0006", "too large for its type"),
+            MissingDigits => ("L0007", "no digits"),
+            MalformedFloat => ("L0008", "malformed float"),
+            InvalidSuffix => ("L0009", "invalid suffix"),
+            UnknownEscape(_) => ("L0010", "unknown escape"),
+            InvalidUnicodeEscape(_) => ("L0011", "invalid escape"),
+            EmptyChar => ("L0012", "empty char"),
+            UnterminatedChar => ("L0013", "unterminated char"),
+            MultiCharLiteral => ("L0014", "more than one codepoint"),
+            InconsistentDedent => ("L0015", "dedent to no enclosing block"),
+            MixedScriptIdent => ("L0016", "mixes several scripts"),
+            ConfusableIdent(_) => ("L0017", "confusable identifier"),
+            TabIndent => ("L0018", "indented with a tab"),
+            EmptyInterpolation => ("L0019", "no expression"),
+            TrailingSeparator => ("L0020", "trailing `_`"),
+            FloatOutOfRange => ("L0021", "too large for its type"),
+            InvalidDigit => ("L0022", "invalid digit"),
+        };
+        let mut diagnostic = if self.reason.is_warning() {
+            Diagnostic::warning(self.reason.to_string())
+        } else {
+            Diagnostic::error(self.reason.to_string())
         };
+        let at = |loc: Loc| Span::new(self.span.file, loc, loc.advanced(" "));
+        let span = match self.reason {
+            // The `\` and the char following it.
+            UnknownEscape(loc) | InvalidUnicodeEscape(loc) => Span::new(self.span.file, loc, loc.advanced("  ")),
+            // The comment spans to the end of the input.
+            UnterminatedComment(_) => Span::new(self.span.file, self.span.end, self.span.end),
+            _ => self.span,
+        };
+        diagnostic = diagnostic.with_code(code).with_label(span, label);
+        match &self.reason {
+            MismatchedDelimiter(open, _) => diagnostic.with_secondary(at(*open), "opened here"),
+            UnterminatedComment(open) => diagnostic.with_secondary(at(*open), "comment opened here"),
+            InvalidSuffix => diagnostic.with_help("use one of `i8` to `i64`, `u8` to `u64`, `f16`, `f32` or `f64`"),
+            ConfusableIdent(ascii) => diagnostic.with_help(format!("write `{ascii}` if it is meant")),
+            _ => diagnostic,
+        }
+    }
+}
+
+fn lex(input: &str) -> Result<LexedBuffer> {
+    Lexer::new(input).lex()
+}
+
+fn lex_lossless(input: &str) -> Result<LexedBuffer> {
+    Lexer::new(input).lossless().lex()
+}
+
+/// The entry point of the lexer. It either lexes the whole input into a `LexedBuffer`
+/// or yields the tokens one at a time as an `Iterator`, lexing only what is asked for.
+pub struct Lexer<'a> {
+    source: &'a str,
+    progress: Option<Progress<'a>>,
+    /// The next token to yield.
+    next: TkHandle,
+}
+
+enum Progress<'a> {
+    Lexing(LexerState<'a>),
+    /// The input is exhausted or lexing stopped on the error.
+    Done(LexedBuffer, Option<LexError>),
+}
+
+/// A token yielded by the `Lexer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TkKind,
+    pub span: Span,
+    pub text: &'a str,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        let state = LexerState::new(input);
+        Self { source: input, progress: Some(Progress::Lexing(state)), next: 0 }
+    }
+
+    /// Keep the whitespaces and new lines as trivia so the source can be rebuilt from the
+    /// tokens. Must be set before lexing, panics otherwise.
+    pub fn lossless(self) -> Self {
+        self.configure(LexerState::keep_trivia)
+    }
+
+    /// Emit `Indent`, `Dedent` and `Newline` tokens following the offside rule. Must be set
+    /// before lexing, panics otherwise.
+    pub fn with_layout(self) -> Self {
+        self.configure(LexerState::with_layout)
+    }
+
+    /// Set the file of the spans. Must be set before lexing, panics otherwise.
+    pub fn in_file(self, file: FileId) -> Self {
+        self.configure(|mut state| {
+            state.file = file;
+            state
+        })
+    }
+
+    fn configure(mut self, f: impl FnOnce(LexerState<'a>) -> LexerState<'a>) -> Self {
+        match self.progress.take() {
+            Some(Progress::Lexing(state)) if state.tokens.is_empty() => {
+                self.progress = Some(Progress::Lexing(f(state)));
+            }
+            _ => panic!("Configured a lexer after lexing."),
+        }
+        self
+    }
+
+    /// Lex the rest of the input, the tokens already yielded are part of the buffer.
+    pub fn lex(self) -> Result<LexedBuffer> {
+        match self.progress.expect("The lexer is always in progress between calls.") {
+            Progress::Lexing(mut state) => loop {
+                state.trim_whitespaces();
+                let Some(c) = state.input.next() else {
+                    return state.finish();
+                };
+                if let Err(err) = lex_token(&mut state, c) {
+                    return Err(state.stop(err));
+                }
+            },
+            Progress::Done(lexed, None) => Ok(lexed),
+            Progress::Done(lexed, Some(err)) => Err(Box::new((err, lexed))),
+        }
+    }
+
+    /// Lex the next token, or finish when the input is exhausted.
+    fn advance(mut state: LexerState<'a>) -> Progress<'a> {
         state.trim_whitespaces();
+        let Some(c) = state.input.next() else {
+            return match state.finish() {
+                Ok(lexed) => Progress::Done(lexed, None),
+                Err(stopped) => {
+                    let (err, partial) = *stopped;
+                    Progress::Done(partial, Some(err))
+                }
+            };
+        };
+        match lex_token(&mut state, c) {
+            Ok(()) => Progress::Lexing(state),
+            Err(err) => {
+                let (err, partial) = *state.stop(err);
+                Progress::Done(partial, Some(err))
+            }
+        }
     }
-    Ok(state.finalize())
 }
 
-fn lex_identifier(mut state: LexerState) -> Result<LexerState>{
-    state.input.skip_while(pred::ident_char);
-    Ok(state.push_token(Ident))
+impl<'a> Iterator for Lexer<'a> {
+    /// The tokens in order, ended by the error that stopped lexing if any.
+    type Item = std::result::Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = self.source;
+        loop {
+            let (kinds, spans) = match self.progress.as_mut()? {
+                Progress::Lexing(state) => (&state.tokens[..], &state.spans[..]),
+                Progress::Done(lexed, err) if self.next >= lexed.nb_tokens() => {
+                    return err.take().map(Err);
+                }
+                Progress::Done(lexed, _) => (&lexed.kinds[..], &lexed.spans[..]),
+            };
+            if self.next < kinds.len() {
+                let (kind, span) = (kinds[self.next], spans[self.next]);
+                let range: std::ops::Range<_> = span.into();
+                self.next += 1;
+                return Some(Ok(Token { kind, span, text: &source[range] }));
+            }
+            let Some(Progress::Lexing(state)) = self.progress.take() else {
+                unreachable!("Only a lexer in progress can run out of tokens.")
+            };
+            self.progress = Some(Self::advance(state));
+        }
+    }
 }
 
-fn lex_ident_or_keyword(mut state: LexerState, c: char) -> Result<LexerState> {
-    let mut kw = String::from(c);
-    while let Some(c) = state.input.next_if(pred::ident_char) {
-        kw.push(c);
+/// Lex the token starting with `c`.
+fn lex_token(state: &mut LexerState, c: char) -> Step {
+    // Comments don't count for the layout.
+    if !(c == '-' && matches!(state.input.peek(), Some('-' | '{'))) {
+        state.layout();
     }
-    state = if let Some(kw) = KEYWORD_MAP.get(&kw) {
-        state.push_token(KeyWord(*kw))
+    match c {
+        ':' => state.push_token(Colon),
+        '=' => state.push_token(Equals),
+        '(' => state.push_token(Lpar),
+        ')' => state.push_token(Rpar),
+        '{' => state.push_token(Lbrace),
+        '}' if state.closes_interpolation() => resume_string(state)?,
+        '}' => state.push_token(Rbrace),
+        '[' => state.push_token(Lbracket),
+        ']' => state.push_token(Rbracket),
+        '|' if state.input.next_is('>') => state.push_token(Pipe),
+        '|' if state.input.next_is('-') => state.push_token(Fork),
+        '|' => state.push_token(Bar),
+        '@' => state.push_token(At),
+        '$' => state.push_token(Dollar),
+        '<' if state.input.next_is('|') => state.push_token(RevPipe),
+        '<' => state.push_token(LT),
+        '>' => state.push_token(GT),
+        '.' => lex_dot(state),
+        ',' => state.push_token(Comma),
+        ';' => state.push_token(Semicolon),
+        '+' => state.push_token(Plus),
+        '*' => state.push_token(Star),
+        '?' => state.push_token(Question),
+        '/' if state.input.next_is('/') => state.push_token(Merge),
+        '/' => state.push_token(Slash),
+        '"' => lex_string(state, false)?,
+        'r' if state.input.next_is('"') => lex_string(state, true)?,
+        '\'' => lex_char(state),
+        '-' => resolve_comment(state)?,
+        _ if pred::ident_start(&c) => lex_ident_or_keyword(state, c),
+        '0' if state.input.next_is('b') => lex_bin_int(state),
+        '0' if state.input.next_is('x') => lex_hex_int(state),
+        _ if c.is_numeric() => lex_float_or_int(state, c),
+        _ => state.push_token(Unrecognized),
+    };
+    Ok(())
+}
+
+fn lex_dot(state: &mut LexerState) {
+    let kind = if state.input.next_is('.') {
+        if state.input.next_is('.') { Ellipsis } else { DotDot }
+    } else if state.input.next_is('_') {
+        DotUnderscore
     } else {
-        state.push_token(Ident)
+        Dot
     };
-    Ok(state)
+    state.push_token(kind)
 }
 
-fn lex_string(mut state: LexerState) -> Result<LexerState> {
-    state = loop {
-        state = match state.input.next() {
-            None => state.error_out(UnexpectedEOI)?,
-            Some('/') if state.input.skip_next('"') => state,
-            Some('"') => break state.push_token(Litteral(StringLit)),
-            _ => state
+/// Lex an identifier following UAX #31, non ASCII identifiers are normalized to NFC
+/// before looking for a keyword.
+fn lex_ident_or_keyword(state: &mut LexerState, c: char) {
+    // ASCII identifiers are read byte by byte, the other chars are checked one at a time.
+    if c.is_ascii() {
+        state.input.eat_ascii(|b| b.is_ascii_alphanumeric() || b == b'_');
+    }
+    state.input.skip_while(pred::ident_char);
+    let text = state.input.since(state.start);
+    let mut name = std::borrow::Cow::Borrowed(text);
+    if !text.is_ascii() {
+        if !is_nfc(text) {
+            name = text.nfc().collect::<String>().into();
         }
-    };
-    Ok(state)
+        if !name.is_single_script() {
+            state.push_err(MixedScriptIdent);
+        } else if let Some(lookalike) = ascii_lookalike(&name) {
+            state.push_err(ConfusableIdent(lookalike.into()));
+        }
+    }
+    match KEYWORD_MAP.get(&name) {
+        Some(kw) => state.push_named(KeyWord(*kw), &name),
+        None => state.push_named(Ident, &name),
+    }
+}
+
+/// The ASCII identifier a non ASCII one looks like, if any.
+fn ascii_lookalike(ident: &str) -> Option<String> {
+    let lookalike: String = skeleton(ident).collect();
+    (lookalike.is_ascii() && lookalike != ident).then_some(lookalike)
 }
 
-fn lex_float_or_int(mut state: LexerState) -> Result<LexerState> {
-    let mut kind = Int;
-    while let Some(x) = state.input.next_if(pred::number_char) {
+/// Lex a decimal litteral, digits can be separated by `_` and the litteral can end
+/// with a type suffix as in `1_000u32` or `0.5f16`.
+fn lex_float_or_int(state: &mut LexerState, c: char) {
+    let mut txt = String::from(c);
+    let mut is_float = false;
+    let mut prev = c;
+    while let Some(x) = state.input.peek() {
+        // A dot not followed by a digit is an operator as in `1..2`.
+        let accepted = match x {
+            '.' => state.input.peek_nth(1).is_some_and(|c| c.is_ascii_digit()),
+            _ => pred::number_char(&x) || pred::separator(&x, prev),
+        };
+        if !accepted {
+            break;
+        }
+        if prev == '_' && !(x.is_ascii_digit() || x == '_') {
+            state.push_err(TrailingSeparator);
+        }
+        state.input.next();
+        prev = x;
+        if x == '_' {
+            continue;
+        }
+        txt.push(x);
         if "eE.".contains(x) {
-            state.input.skip_if(|c| "-+".contains(*c) && x != '.');
+            is_float = true;
+            if let Some(sign) = state.input.next_if(|c| "-+".contains(*c) && x != '.') {
+                txt.push(sign);
+                prev = sign;
+            }
         };
-        kind = Float;
     }
-    Ok(state.push_token(Litteral(kind)))
+    if prev == '_' {
+        state.push_err(TrailingSeparator);
+    }
+    let default = if is_float { Float(None) } else { Int(None) };
+    let negated = state.negated();
+    let (lit, value) = match lex_suffix(state) {
+        Ok(None) => (default, decode_number(&txt, default, negated)),
+        Ok(Some(Int(_))) if is_float => (default, Err(InvalidSuffix)),
+        Ok(Some(lit)) => (lit, decode_number(&txt, lit, negated)),
+        Err(reason) => (default, Err(reason)),
+    };
+    state.push_literal_or_err(lit, value)
+}
+
+/// Whether a token after `kinds` is negated by the last of them, a `-` after an operand
+/// is a subtraction as in `a - 128i8`.
+fn negates_next(kinds: &[TkKind]) -> bool {
+    let mut before = kinds.iter().rev().filter(|k| !matches!(k, Comment | DocComment));
+    before.next() == Some(&Dash) && !before.next().is_some_and(TkKind::ends_operand)
 }
 
-fn lex_bin_int(mut state: LexerState) -> Result<LexerState> {
-    state.input.skip_while(pred::bin_int_char);
-    Ok(state.push_token(Litteral(Int)))
+/// Decode a decimal number with the type of the litteral `lit`, `negated` if it follows
+/// a `-` negating it.
+fn decode_number(txt: &str, lit: Lit, negated: bool) -> std::result::Result<LitValue, Reason> {
+    match lit {
+        Float(ty) => {
+            let value = txt.parse::<f64>().map_err(|_| MalformedFloat)?;
+            if ty.unwrap_or(FloatTy { bits: 64 }).holds(value) {
+                Ok(LitValue::Float(value))
+            } else {
+                Err(FloatOutOfRange)
+            }
+        }
+        // Decimal integers default to signed and only fall back to unsigned when they do not fit.
+        Int(None) => txt.parse::<i64>().map(LitValue::Int)
+            .or_else(|_| txt.parse::<u64>().map(LitValue::UInt))
+            .map_err(|_| IntOutOfRange),
+        Int(Some(ty)) => txt
+            .parse::<u64>()
+            .map_err(|_| IntOutOfRange)
+            .and_then(|v| int_value(v, ty, negated)),
+        _ => unreachable!("Decoded a number as a {lit} litteral."),
+    }
 }
 
-fn lex_hex_int(mut state: LexerState) -> Result<LexerState> {
-    state.input.skip_while(pred::hexa_int_char);
-    Ok(state.push_token(Litteral(Int)))
+/// The value of an integer litteral of type `ty`. The minimum of a signed type can be
+/// written after a `-`, the magnitude of `i64::MIN` is kept unsigned.
+fn int_value(value: u64, ty: IntTy, negated: bool) -> std::result::Result<LitValue, Reason> {
+    match ty {
+        _ if !ty.holds(value, negated) => Err(IntOutOfRange),
+        IntTy { signed: true, .. } => Ok(i64::try_from(value).map_or(LitValue::UInt(value), LitValue::Int)),
+        IntTy { signed: false, .. } => Ok(LitValue::UInt(value)),
+    }
 }
 
-fn resolve_comment(mut state: LexerState) -> Result<LexerState> {
-    state = match state.input.peek() {
+/// Lex the letters directly following a number, they must be a scalar type suffix.
+fn lex_suffix(state: &mut LexerState) -> std::result::Result<Option<Lit>, Reason> {
+    let Some(first) = state.input.next_if(char::is_ascii_alphabetic) else {
+        return Ok(None);
+    };
+    let mut suffix = String::from(first);
+    while let Some(c) = state.input.next_if(pred::ident_char) {
+        suffix.push(c);
+    }
+    Lit::from_suffix(&suffix).map(Some).ok_or(InvalidSuffix)
+}
+
+fn lex_bin_int(state: &mut LexerState) {
+    lex_radix_int(state, 2, pred::bin_int_char)
+}
+
+fn lex_hex_int(state: &mut LexerState) {
+    lex_radix_int(state, 16, pred::hexa_int_char)
+}
+
+fn lex_radix_int(state: &mut LexerState, radix: u32, digit: fn(&char) -> bool) {
+    let mut digits = String::new();
+    let mut prev = None;
+    while let Some(d) = state.input.next_if(|c| digit(c) || *c == '_') {
+        if d != '_' {
+            digits.push(d);
+        }
+        prev = Some(d);
+    }
+    if prev == Some('_') {
+        state.push_err(TrailingSeparator);
+    }
+    let negated = state.negated();
+    // A digit or a letter out of the base, as the `2` of `0b102`, doesn't start another
+    // token. Only the letters of the integer suffixes can follow the digits.
+    let invalid_digit = !digits.is_empty()
+        && state.input.peek().is_some_and(|c| c.is_ascii_alphanumeric() && !"iuf".contains(c));
+    if invalid_digit {
+        state.input.skip_while(pred::ident_char);
+    }
+    let (lit, value) = match lex_suffix(state) {
+        _ if digits.is_empty() => (Int(None), Err(MissingDigits)),
+        _ if invalid_digit => (Int(None), Err(InvalidDigit)),
+        Ok(Some(Float(_))) | Err(_) => (Int(None), Err(InvalidSuffix)),
+        Ok(suffix) => {
+            let lit = suffix.unwrap_or(Int(None));
+            let value = u64::from_str_radix(&digits, radix).map_err(|_| IntOutOfRange);
+            let value = match lit {
+                Int(Some(ty)) => value.and_then(|v| int_value(v, ty, negated)),
+                _ => value.map(LitValue::UInt),
+            };
+            (lit, value)
+        }
+    };
+    state.push_literal_or_err(lit, value)
+}
+
+fn resolve_comment(state: &mut LexerState) -> Step {
+    match state.input.peek() {
         Some('-') => {
-            //  single line comment '--'
-            state.input.skip_while(|c| *c != '\n');
-            state.push_token(Comment)
-        }
-        Some('{') => loop {
-            // Multiline comment '-{ ... }-'.
-            eprintln!("multi");
-            state.input.skip_while(|c| *c != '}');
             state.input.next();
-            if state.input.next_is('-') {
-                break state.push_token(Comment);
+            if state.input.next_is('|') {
+                // Doc comment '--|'.
+                let doc = state.input.eat_line();
+                let doc = doc.strip_prefix(' ').unwrap_or(doc).trim_end();
+                state.push_doc(doc.into())
+            } else {
+                //  single line comment '--'
+                state.input.eat_line();
+                state.push_token(Comment)
+            }
+        }
+        Some('{') => {
+            // Multiline comment '-{ ... }-', they can be nested.
+            // Doc comments '-{| ... }-' keep their text.
+            state.input.next();
+            let is_doc = state.input.next_is('|');
+            let body = state.input.loc();
+            let mut depth = 1usize;
+            while depth > 0 {
+                state.input.eat_ascii(|b| !matches!(b, b'-' | b'}' | b'\n'));
+                match state.input.next() {
+                    None => {
+                        let open = state.start;
+                        return Err(state.error(UnterminatedComment(open)));
+                    }
+                    Some('-') if state.input.next_is('{') => depth += 1,
+                    Some('}') if state.input.next_is('-') => depth -= 1,
+                    Some(_) => (),
+                }
+            }
+            if is_doc {
+                // The text of the doc without the closing `}-`.
+                let text = state.input.since(body);
+                let mut doc = StringBuilder::default();
+                for (i, line) in text[..text.len() - 2].split('\n').enumerate() {
+                    if i > 0 {
+                        doc.new_line();
+                    }
+                    line.strip_suffix('\r').unwrap_or(line).chars().for_each(|c| doc.push(c));
+                }
+                state.push_doc(doc.finish(true).concat().trim_end().into())
+            } else {
+                state.push_token(Comment)
             }
-        },
+        }
+        Some('>') => {
+            state.input.next();
+            state.push_token(Arrow)
+        }
         _ => state.push_token(Dash),
     };
-    Ok(state)
+    Ok(())
 }
 
 /// Lexing state. Call new with an input to create, and lex to lex the input.
 #[derive(Debug)]
 pub struct LexerState<'a> {
-    mode: LexMode,
+    file: FileId,
+    modes: Vec<LexMode>,
     source: IStr,
-    input: LexerInput<'a>,
+    input: Cursor<'a>,
     tokens: Vec<TkKind>,
     spans: Vec<Span>,
-    delim_stack: Vec<TkKind>,
+    literals: Vec<(TkHandle, LitValue)>,
+    docs: Vec<(TkHandle, IStr)>,
+    /// The interned NFC name of each identifier and keyword.
+    symbols: Vec<Option<Symbol>>,
+    delim_stack: DelimStack,
     start: Loc,
-    minor_errors: Vec<LexError>
+    minor_errors: Vec<LexError>,
+    keep_trivia: bool,
+    trivia: Vec<Trivia>,
+    /// For each token, the index in `trivia` of its leading trivia.
+    leading_trivia: Vec<usize>,
+    /// The index in `trivia` of the leading trivia of the next token.
+    trivia_mark: usize,
+    layout: bool,
+    /// The columns of the enclosing blocks, empty before the first token.
+    indents: Vec<usize>,
+    /// Whether no token but comments was pushed since the last new line.
+    line_start: bool,
 }
 
 impl<'a> LexerState<'a> {
     pub fn new(input: &'a str) -> LexerState<'a> {
+        Self::new_at(input, Loc::default())
+    }
+
+    /// Start lexing `input` from `start`.
+    fn new_at(input: &'a str, start: Loc) -> LexerState<'a> {
         let source = input.into();
-        let input = LexerInput::new(input.chars(), Loc::default());
-        let start = *input.get::<Loc>();
+        let input = Cursor::new(input, start);
         Self {
-            mode: LexMode::Default,
+            file: FileId::default(),
+            modes: vec![LexMode::Default],
             source,
             input,
             start,
-            delim_stack: vec![],
+            delim_stack: DelimStack::default(),
             tokens: vec![],
             spans: vec![],
+            literals: vec![],
+            docs: vec![],
+            symbols: vec![],
             minor_errors: vec![],
+            keep_trivia: false,
+            trivia: vec![],
+            leading_trivia: vec![],
+            trivia_mark: 0,
+            layout: false,
+            indents: vec![],
+            line_start: true,
         }
     }
 
-    fn push_token(mut self, kind: TkKind) -> Self {
+    /// Emit the layout tokens.
+    fn with_layout(mut self) -> Self {
+        self.layout = true;
+        self
+    }
+
+    /// Record the whitespaces and new lines between tokens.
+    fn keep_trivia(mut self) -> Self {
+        self.keep_trivia = true;
+        self
+    }
+
+    fn push_token(&mut self, kind: TkKind) {
+        let end = self.input.loc();
+        let span = Span::new(self.file, self.start, end);
+        let token = self.tokens.len();
+        let errors = &mut self.minor_errors;
+        self.delim_stack.balance(kind, span, |reason| errors.push(LexError { span, reason, token }));
         self.tokens.push(kind);
-        // TODO Handle balanced delimiters.
-        let end = *self.input.get();
-        self.spans.push(Span::new(self.start, end));
+        self.spans.push(span);
+        self.symbols.push(None);
+        if self.keep_trivia {
+            self.leading_trivia.push(self.trivia_mark);
+            self.trivia_mark = self.trivia.len();
+        }
         self.start = end;
-        self
     }
 
-    fn push_err(mut self, reason: Reason) -> Self {
-        self.minor_errors.push(LexError {
-            loc: *self.input.get::<Loc>(),
+    /// Emit the layout tokens before the first token of a line, the new lines inside
+    /// delimiters are ignored. Lines must be indented with spaces.
+    fn layout(&mut self) {
+        if !self.layout || !self.line_start {
+            return;
+        }
+        self.line_start = false;
+        if !self.delim_stack.0.is_empty() {
+            return;
+        }
+        let before = &self.source[..self.start.pos];
+        let indent = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        if indent.contains('\t') {
+            self.record_err(Span::new(self.file, self.start, self.start), TabIndent);
+        }
+        let col = self.start.col;
+        let Some(&top) = self.indents.last() else {
+            self.indents.push(col);
+            return;
+        };
+        if col > top {
+            self.indents.push(col);
+            return self.push_virtual(Indent);
+        }
+        while self.indents.len() > 1 && col < *self.indents.last().unwrap() {
+            self.indents.pop();
+            self.push_virtual(Dedent);
+        }
+        if col != *self.indents.last().unwrap() {
+            self.record_err(Span::new(self.file, self.start, self.start), InconsistentDedent);
+        }
+        self.push_virtual(Newline)
+    }
+
+    /// Push a zero width layout token before the token starting.
+    fn push_virtual(&mut self, kind: TkKind) {
+        self.tokens.push(kind);
+        self.spans.push(Span::new(self.file, self.start, self.start));
+        self.symbols.push(None);
+        if self.keep_trivia {
+            // The trivia stays with the next token.
+            self.leading_trivia.push(self.trivia_mark);
+        }
+    }
+
+    /// Close what is still open at the end of the input. A string can't end in one of its
+    /// interpolations.
+    fn finish(mut self) -> Result<LexedBuffer> {
+        if self.modes.iter().any(|mode| matches!(mode, LexMode::Interpolation(_))) {
+            let err = self.error(UnexpectedEOI);
+            return Err(self.stop(err));
+        }
+        for _ in 1..self.indents.len() {
+            self.push_virtual(Dedent);
+        }
+        for (_, span) in std::mem::take(&mut self.delim_stack.0) {
+            self.record_err(span, UnclosedDelimiter(span.start));
+        }
+        Ok(self.finalize())
+    }
+
+    /// Whether the token being lexed follows a `-` negating it.
+    fn negated(&self) -> bool {
+        negates_next(&self.tokens)
+    }
+
+    /// Whether a `}` closes the interpolation being lexed rather than a record.
+    fn closes_interpolation(&self) -> bool {
+        matches!(self.modes.last(), Some(LexMode::Interpolation(_)))
+            && matches!(
+                self.delim_stack.0.iter().rev().find(|(k, _)| matches!(k, Lbrace | StrHead)),
+                Some((StrHead, _))
+            )
+    }
+
+    /// Record the value of a litteral token pushed earlier.
+    fn record_literal(&mut self, handle: TkHandle, value: LitValue) {
+        let i = self.literals.partition_point(|(h, _)| *h < handle);
+        self.literals.insert(i, (handle, value));
+    }
+
+    /// Push a litteral token and record its decoded value.
+    fn push_literal(&mut self, lit: Lit, value: LitValue) {
+        self.literals.push((self.tokens.len(), value));
+        self.push_token(Litteral(lit))
+    }
+
+    /// Push an identifier or keyword token with its interned name.
+    fn push_named(&mut self, kind: TkKind, name: &str) {
+        self.push_token(kind);
+        *self.symbols.last_mut().unwrap() = Some(Symbol::intern(name));
+    }
+
+    /// Push a doc comment token and record its text.
+    fn push_doc(&mut self, doc: IStr) {
+        self.docs.push((self.tokens.len(), doc));
+        self.push_token(DocComment)
+    }
+
+    /// Push a litteral token, recording the decoding error as a minor error if there is one.
+    fn push_literal_or_err(&mut self, lit: Lit, value: std::result::Result<LitValue, Reason>) {
+        match value {
+            Ok(value) => self.push_literal(lit, value),
+            Err(reason) => {
+                self.push_err(reason);
+                self.push_token(Litteral(lit))
+            }
+        }
+    }
+
+    /// The span from the start of the current token to the current position.
+    fn current_span(&self) -> Span {
+        Span::new(self.file, self.start, self.input.loc())
+    }
+
+    fn record_err(&mut self, span: Span, reason: Reason) {
+        let token = self.tokens.len();
+        self.minor_errors.push(LexError { span, reason, token });
+    }
+
+    fn push_err(&mut self, reason: Reason) {
+        self.record_err(self.current_span(), reason);
+    }
+
+    /// The error stopping the lexing of the current token.
+    fn error(&self, reason: Reason) -> LexError {
+        LexError {
+            span: self.current_span(),
             reason,
-        });
-        self
+            token: self.tokens.len(),
+        }
     }
 
-    fn error_out<T>(self, reason: Reason) -> Result<T> {
-        Err((
-            LexError {
-                loc: *self.input.get::<Loc>(),
-                reason,
-            },
-            LexedBuffer {
-                source: self.source,
-                kinds: self.tokens.into(),
-                spans: self.spans.into(),
-                minor_errors: self.minor_errors.into(),
-            },
-        ))
+    /// Stop lexing on `err`, keeping the tokens lexed so far.
+    fn stop(self, err: LexError) -> Box<(LexError, LexedBuffer)> {
+        let mut partial = self.finalize();
+        partial.complete = false;
+        Box::new((err, partial))
     }
 
-    fn finalize(self) -> LexedBuffer {
+    fn finalize(mut self) -> LexedBuffer {
+        if self.keep_trivia {
+            // The trivia left after the last token.
+            self.leading_trivia.push(self.trivia_mark);
+        }
         LexedBuffer {
+            file: self.file,
             source: self.source,
             kinds: self.tokens.into(),
             spans: self.spans.into(),
+            literals: self.literals.into(),
+            docs: self.docs.into(),
+            symbols: self.symbols.into(),
             minor_errors: self.minor_errors.into(),
+            trivia: self.trivia.into(),
+            leading_trivia: self.leading_trivia.into(),
+            layout: self.layout,
+            complete: true,
         }
     }
 
     fn trim_whitespaces(&mut self) {
-        self.input.skip_while(|c| c.is_whitespace())
+        let line = self.input.loc().row;
+        if self.keep_trivia {
+            self.record_trivia();
+        } else {
+            self.input.skip_while(|c| c.is_whitespace());
+        }
+        self.start = self.input.loc();
+        self.line_start |= self.start.row != line;
+    }
+
+    /// Skip the whitespaces splitting them in trivia at each new line.
+    fn record_trivia(&mut self) {
+        loop {
+            let start = self.input.loc();
+            let newline = self.input.next_is('\n')
+                || (self.input.next_is('\r') && self.input.next_is('\n'));
+            let kind = if newline {
+                TriviaKind::Newline
+            } else {
+                self.input.skip_while(|&c| c.is_whitespace() && c != '\n' && c != '\r');
+                TriviaKind::Whitespace
+            };
+            let end = self.input.loc();
+            if end == start {
+                break;
+            }
+            self.trivia.push(Trivia { kind, span: Span::new(self.file, start, end) });
+        }
     }
 
 }
 
+/// The delimiters opened and not closed yet with their span.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct DelimStack(Vec<(TkKind, Span)>);
+
+impl DelimStack {
+    /// Keep track of the opened delimiters, recording the unbalanced ones as minor errors.
+    /// A `|` closes the enum bar on top of the stack and opens a new one otherwise.
+    /// Interpolations are tracked with a `StrHead` entry.
+    fn balance(&mut self, kind: TkKind, span: Span, record_err: impl FnMut(Reason)) {
+        let closes_bar = kind == Bar && matches!(self.0.last(), Some((Bar, _)));
+        if kind.is_close_delim() || closes_bar {
+            self.close(kind, span, record_err);
+        }
+        if kind.is_open_delim() || (kind == Bar && !closes_bar) {
+            let kind = if kind == StrMiddle { StrHead } else { kind };
+            self.0.push((kind, span));
+        }
+    }
+
+    fn close(&mut self, kind: TkKind, span: Span, mut record_err: impl FnMut(Reason)) {
+        let open = kind.get_matching_delim();
+        // Delimiters opened before an interpolation can't be closed from inside it.
+        let floor = match open {
+            StrHead => 0,
+            _ => self.0.iter().rposition(|(k, _)| *k == StrHead).map_or(0, |i| i + 1),
+        };
+        let matching = self.0[floor..]
+            .iter()
+            .rposition(|(k, _)| *k == open)
+            .map(|i| i + floor);
+        match (self.0[floor..].last(), matching) {
+            (None, _) => record_err(UnbalancedDelimiter(span.start)),
+            (Some((top, _)), _) if *top == open => {
+                self.0.pop();
+            }
+            // The delimiters opened after the matching one are left unclosed.
+            (Some(_), Some(i)) => {
+                for (_, open_span) in self.0.split_off(i).into_iter().skip(1) {
+                    record_err(MismatchedDelimiter(open_span.start, span.start));
+                }
+            }
+            // Nothing to close, the delimiter on top may still be closed later.
+            (Some((_, open_span)), None) => {
+                let open_loc = open_span.start;
+                record_err(MismatchedDelimiter(open_loc, span.start));
+            }
+        }
+    }
+}
+
 mod pred {
+    use unicode_xid::UnicodeXID;
+
+    pub fn ident_start(x: &char) -> bool {
+        x.is_xid_start() || *x == '_'
+    }
+
     pub fn ident_char(x: &char) -> bool {
-        x.is_alphanumeric() || *x == '_'
+        x.is_xid_continue()
     }
 
     pub fn number_char(x: &char) -> bool {
@@ -228,6 +940,11 @@ mod pred {
         x.is_ascii_digit() || chars.contains(*x)
     }
 
+    /// A `_` separating the digits of a number, after the char `prev`.
+    pub fn separator(x: &char, prev: char) -> bool {
+        *x == '_' && (prev.is_ascii_digit() || prev == '_')
+    }
+
     pub fn hexa_int_char(x: &char) -> bool {
         x.is_ascii_digit() || "aAbBcCdDeEfF".contains(*x)
     }
@@ -238,15 +955,33 @@ mod pred {
 }
 
 /// Output of the lexing stage.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct LexedBuffer {
+    file: FileId,
     source: IStr,
     kinds: IVec<TkKind>,
     spans: IVec<Span>,
-    minor_errors: IVec<LexError>
+    literals: IVec<(TkHandle, LitValue)>,
+    docs: IVec<(TkHandle, IStr)>,
+    /// The interned NFC name of each identifier and keyword.
+    symbols: IVec<Option<Symbol>>,
+    minor_errors: IVec<LexError>,
+    /// Empty unless lexed in lossless mode.
+    trivia: IVec<Trivia>,
+    /// The index in `trivia` of the leading trivia of each token, and of the trivia
+    /// ending the source.
+    leading_trivia: IVec<usize>,
+    /// Whether the layout tokens were emitted.
+    layout: bool,
+    /// False when lexing stopped on an error.
+    complete: bool,
 }
 
 impl LexedBuffer {
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
     pub fn source(&self) -> IStr {
         self.source.clone()
     }
@@ -259,19 +994,23 @@ impl LexedBuffer {
         self.spans.clone()
     }
 
+    pub fn minor_errors(&self) -> IVec<LexError> {
+        self.minor_errors.clone()
+    }
+
     pub fn nb_tokens(&self) -> usize {
         self.kinds.len()
     }
 
     pub fn next_handle(&self, handle: TkHandle) -> Option<TkHandle> {
-        if handle >= self.kinds.len() {
+        if handle + 1 >= self.kinds.len() {
             return None;
         } 
         Some(handle + 1)
     }
 
     pub fn first(&self) -> Option<TkHandle> {
-        if self.kinds.len() == 0 {
+        if self.kinds.is_empty() {
             None
         } else {
             Some(0usize)
@@ -282,12 +1021,97 @@ impl LexedBuffer {
         &self.kinds[id]
     }
 
+    /// The decoded value of a litteral token, `None` if the token isn't a litteral
+    /// or if its value could not be decoded.
+    pub fn get_literal(&self, id: TkHandle) -> Option<&LitValue> {
+        self.literals
+            .binary_search_by_key(&id, |(h, _)| *h)
+            .ok()
+            .map(|i| &self.literals[i].1)
+    }
+
+    /// The interned name of an identifier or keyword, two identifiers with the same name
+    /// have the same symbol even if their source text is encoded differently.
+    pub fn get_symbol(&self, id: TkHandle) -> Option<Symbol> {
+        self.symbols[id]
+    }
+
+    pub fn symbols(&self) -> IVec<Option<Symbol>> {
+        self.symbols.clone()
+    }
+
+    /// The NFC name of an identifier or keyword.
+    pub fn get_ident(&self, id: TkHandle) -> Option<&'static str> {
+        self.get_symbol(id).map(Symbol::as_str)
+    }
+
+    /// The text of a doc comment token without its markers.
+    pub fn get_doc(&self, id: TkHandle) -> Option<IStr> {
+        self.docs
+            .binary_search_by_key(&id, |(h, _)| *h)
+            .ok()
+            .map(|i| self.docs[i].1.clone())
+    }
+
+    /// The documentation attached to the token `id`: the text of the doc comments
+    /// directly preceding it, joined by new lines. Layout tokens are skipped.
+    pub fn doc_of(&self, id: TkHandle) -> Option<IStr> {
+        let mut docs: Vec<_> = (0..id)
+            .rev()
+            .take_while(|&h| self.kinds[h] == DocComment || self.kinds[h].is_layout())
+            .filter_map(|h| self.get_doc(h))
+            .collect();
+        if docs.is_empty() {
+            return None;
+        }
+        docs.reverse();
+        Some(docs.join("\n").into())
+    }
+
     pub fn get_token_txt(&self, id: TkHandle) -> IStr    {
         let span = self.spans[id];
         let range: std::ops::Range<_> = span.into();
         self.source[range].into()
     }
 
+    /// Whether the whitespaces between tokens were kept.
+    pub fn has_trivia(&self) -> bool {
+        !self.leading_trivia.is_empty()
+    }
+
+    /// The trivia preceding the token `id`, `id` being `nb_tokens()` for the trivia
+    /// ending the source. Empty unless lexed in lossless mode.
+    pub fn leading_trivia(&self, id: TkHandle) -> &[Trivia] {
+        if !self.has_trivia() {
+            return &[];
+        }
+        let end = self.leading_trivia.get(id + 1).copied().unwrap_or(self.trivia.len());
+        &self.trivia[self.leading_trivia[id]..end]
+    }
+
+    pub fn get_trivia_txt(&self, trivia: &Trivia) -> IStr {
+        let range: std::ops::Range<_> = trivia.span.into();
+        self.source[range].into()
+    }
+
+    /// Rebuild the source from the tokens and their trivia.
+    pub fn lossless_text(&self) -> String {
+        let mut text = String::new();
+        for id in 0..=self.kinds.len() {
+            for trivia in self.leading_trivia(id) {
+                text.push_str(&self.get_trivia_txt(trivia));
+            }
+            if id < self.kinds.len() {
+                text.push_str(&self.get_token_txt(id));
+            }
+        }
+        text
+    }
+
+    pub fn line_index(&self) -> LineIndex {
+        LineIndex::new(self.source.clone())
+    }
+
     pub fn print_lexed(&self) {
         for id in 0..self.kinds.len() {
             println!("kind: {} source: {}", self.get_kind(id) , self.get_token_txt(id));
@@ -382,6 +1206,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_match_arms() {
+        let lexed = lex("match a with A x -> x; _ -> 0;").unwrap();
+        match_kinds(
+            lexed.kinds,
+            vec![
+                KeyWord(Match), // match
+                Ident,          // a
+                KeyWord(With),  // with
+                Ident,          // A
+                Ident,          // x
+                Arrow,          // ->
+                Ident,          // x
+                Semicolon,      // ;
+                Ident,          // _
+                Arrow,          // ->
+                Litteral(Int(None)), // 0
+                Semicolon,      // ;
+            ]
+        );
+    }
+
     #[test]
     fn test_comment() {
         let lexed = lex(r#"
@@ -398,7 +1244,7 @@ mod test {
                 Comment,
                 Ident,          // a
                 Equals,         // =
-                Litteral(Int),  // 2
+                Litteral(Int(None)),  // 2
                 Comment,        
                 Ident,          // id 
                 Equals,         // =
@@ -409,6 +1255,85 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_nested_comment() {
+        let lexed = lex(r#"
+            -{ Commented out code:
+               -{ with its own comment }-
+               a = x: x -- and a line comment
+            }-
+            id = x: x
+        "#).unwrap();
+        match_kinds(lexed.kinds, vec![Comment, Ident, Equals, Ident, Colon, Ident]);
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let (err, lexed) = *lex("a = 2\n  -{ open -{ nested }- \n b").unwrap_err();
+        match err.reason() {
+            UnterminatedComment(open) => assert_eq!(open.pos, 8),
+            r => panic!("Unexpected error {r:?}"),
+        }
+        match_kinds(lexed.kinds, vec![Ident, Equals, Litteral(Int(None))]);
+    }
+
+    fn error_locs(lexed: &LexedBuffer) -> Vec<(usize, Option<usize>)> {
+        lexed.minor_errors.iter().map(|e| match e.reason() {
+            UnbalancedDelimiter(close) => (close.pos, None),
+            MismatchedDelimiter(open, close) => (open.pos, Some(close.pos)),
+            UnclosedDelimiter(open) => (open.pos, None),
+            r => panic!("Unexpected error {r:?}"),
+        }).collect()
+    }
+
+    #[test]
+    fn test_balanced_delims() {
+        let lexed = lex(r#"
+            e $ | INT Int, RECORD { a @ Int, b @ [Int] }, LIST [ | A, B | ] |
+            f = (x: (g x) |> h)
+        "#).unwrap();
+        assert!(lexed.minor_errors.is_empty(), "{:?}", lexed.minor_errors);
+    }
+
+    #[test]
+    fn test_unbalanced_delims() {
+        // Stray closing delimiter.
+        assert_eq!(error_locs(&lex("a )").unwrap()), vec![(2, None)]);
+        // Mismatched top, the paren is still closed later.
+        assert_eq!(error_locs(&lex("(a ] b)").unwrap()), vec![(0, Some(3))]);
+        // Delimiters opened after the matching one are left unclosed.
+        assert_eq!(error_locs(&lex("{ ( [ }").unwrap()), vec![(2, Some(6)), (4, Some(6))]);
+        // Unclosed at the end of input.
+        assert_eq!(error_locs(&lex("{ | A, B").unwrap()), vec![(0, None), (2, None)]);
+    }
+
+    #[test]
+    fn test_doc_comment() {
+        let lexed = lex(r#"
+            -- Not a doc.
+            --| Adds one
+            --| to its argument.
+            add_one = a: a + 1
+
+            -{|
+                Doubles a value.
+                    x: x * 2
+            }-
+            double = x: x * 2
+        "#).unwrap();
+        match_kinds(lexed.kinds(), vec![
+            Comment, DocComment, DocComment,
+            Ident, Equals, Ident, Colon, Ident, Plus, Litteral(Int(None)),
+            DocComment,
+            Ident, Equals, Ident, Colon, Ident, Star, Litteral(Int(None)),
+        ]);
+        assert_eq!(lexed.get_doc(0), None);
+        assert_eq!(lexed.get_doc(1).as_deref(), Some("Adds one"));
+        assert_eq!(lexed.doc_of(3).as_deref(), Some("Adds one\nto its argument."));
+        assert_eq!(lexed.doc_of(4), None);
+        assert_eq!(lexed.doc_of(11).as_deref(), Some("Doubles a value.\n    x: x * 2"));
+    }
+
     #[test]
     fn test_literals() {
         let lexed = lex(r#"
@@ -425,20 +1350,430 @@ mod test {
             lexed.kinds,
             vec![
                 // a = 2
-                Ident, Equals, Litteral(Int),
+                Ident, Equals, Litteral(Int(None)),
                 // b = 0b10010
-                Ident, Equals, Litteral(Int), 
+                Ident, Equals, Litteral(Int(None)), 
                 // h = 0xff8ea0
-                Ident, Equals, Litteral(Int),  
+                Ident, Equals, Litteral(Int(None)),  
                 // a = -2
-                Ident, Equals, Dash, Litteral(Int),
+                Ident, Equals, Dash, Litteral(Int(None)),
                 // a = -2e-10
-                Ident, Equals, Dash, Litteral(Float),
+                Ident, Equals, Dash, Litteral(Float(None)),
                 // a = 0.5
-                Ident, Equals, Litteral(Float),
+                Ident, Equals, Litteral(Float(None)),
                 // s = "test"
                 Ident, Equals, Litteral(StringLit),
             ]
         );
     }
+
+    #[test]
+    fn test_literal_values() {
+        let lexed = lex(r#"
+            2 0b10010 0xff8ea0 2e-10 0.5 "te\"st" 18446744073709551615
+        "#).unwrap();
+        let values: Vec<_> = (0..lexed.nb_tokens())
+            .map(|id| lexed.get_literal(id).cloned())
+            .collect();
+        assert_eq!(values, vec![
+            Some(LitValue::Int(2)),
+            Some(LitValue::UInt(0b10010)),
+            Some(LitValue::UInt(0xff8ea0)),
+            Some(LitValue::Float(2e-10)),
+            Some(LitValue::Float(0.5)),
+            Some(LitValue::Str("te\"st".into())),
+            Some(LitValue::UInt(u64::MAX)),
+        ]);
+        assert!(lexed.minor_errors.is_empty());
+    }
+
+    #[test]
+    fn test_literal_errors() {
+        let lexed = lex("0x 1.2.3 1e 18446744073709551616 0xfffffffffffffffff").unwrap();
+        match_kinds(lexed.kinds(), vec![Litteral(Int(None)), Litteral(Float(None)), Litteral(Float(None)), Litteral(Int(None)), Litteral(Int(None))]);
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason()).collect();
+        assert!(matches!(reasons[..], [MissingDigits, MalformedFloat, MalformedFloat, IntOutOfRange, IntOutOfRange]));
+        let spans: Vec<std::ops::Range<usize>> = lexed.minor_errors.iter().map(|e| e.span().into()).collect();
+        assert_eq!(spans, vec![0..2, 3..8, 9..11, 12..32, 33..52]);
+        assert!((0..lexed.nb_tokens()).all(|id| lexed.get_literal(id).is_none()));
+
+        // The digits out of the base are part of the litteral.
+        let lexed = lex("0b102 0xfg 0b1_0x 0b11u8").unwrap();
+        assert_eq!(lexed.nb_tokens(), 4);
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason().clone()).collect();
+        assert_eq!(reasons, vec![InvalidDigit, InvalidDigit, InvalidDigit]);
+        let spans: Vec<std::ops::Range<usize>> = lexed.minor_errors.iter().map(|e| e.span().into()).collect();
+        assert_eq!(spans, vec![0..5, 6..10, 11..17]);
+        assert_eq!(lexed.get_literal(3), Some(&LitValue::UInt(3)));
+    }
+
+    #[test]
+    fn test_number_dots() {
+        let lexed = lex("1..2 3.sqrt 4.5 6...").unwrap();
+        match_kinds(
+            lexed.kinds(),
+            vec![
+                Litteral(Int(None)), DotDot, Litteral(Int(None)),
+                Litteral(Int(None)), Dot, Ident,
+                Litteral(Float(None)),
+                Litteral(Int(None)), Ellipsis,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_literal_suffixes() {
+        let lexed = lex("1_000_000 1.0f32 2u32 0xFFu8 0.5f16 0b1010_0101i16 1e1_0 255u8").unwrap();
+        let u = |bits| Int(Some(IntTy { signed: false, bits }));
+        let f = |bits| Float(Some(FloatTy { bits }));
+        match_kinds(
+            lexed.kinds(),
+            [Int(None), f(32), u(32), u(8), f(16), Int(Some(IntTy { signed: true, bits: 16 })), Float(None), u(8)]
+                .map(Litteral)
+                .to_vec(),
+        );
+        let values: Vec<_> = (0..lexed.nb_tokens()).map(|id| lexed.get_literal(id).cloned().unwrap()).collect();
+        assert_eq!(values, vec![
+            LitValue::Int(1_000_000),
+            LitValue::Float(1.0),
+            LitValue::UInt(2),
+            LitValue::UInt(0xFF),
+            LitValue::Float(0.5),
+            LitValue::Int(0b1010_0101),
+            LitValue::Float(1e10),
+            LitValue::UInt(255),
+        ]);
+        assert!(lexed.minor_errors.is_empty());
+
+        // `0xFFf32` only holds hex digits.
+        let lexed = lex("256u8 128i8 1.5u32 2px 0xFFf32 3_ 0x1_ 1__2 1_.5").unwrap();
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason().clone()).collect();
+        assert_eq!(reasons, vec![
+            IntOutOfRange, IntOutOfRange, InvalidSuffix, InvalidSuffix,
+            TrailingSeparator, TrailingSeparator, TrailingSeparator,
+        ]);
+        assert_eq!(lexed.nb_tokens(), 9);
+
+        let lexed = lex("1.0e400 1e40f32 1e30f32 65504f16 65520f16 1e308").unwrap();
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason().clone()).collect();
+        assert_eq!(reasons, vec![FloatOutOfRange, FloatOutOfRange, FloatOutOfRange]);
+        assert_eq!(lexed.get_literal(3), Some(&LitValue::Float(65504.0)));
+
+        // The minimum of a signed type is written after a `-` negating it, not after a
+        // subtraction.
+        let lexed = lex("[-128i8, -9223372036854775808i64, -0x80i8, -129i8, 128i8] (-128i8) a - 128i8 f -128i8").unwrap();
+        let values: Vec<_> = [2, 5, 8, 17].map(|id| lexed.get_literal(id).cloned().unwrap()).to_vec();
+        assert_eq!(values, vec![LitValue::Int(128), LitValue::UInt(1 << 63), LitValue::Int(0x80), LitValue::Int(128)]);
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason().clone()).collect();
+        assert_eq!(reasons, vec![IntOutOfRange, IntOutOfRange, IntOutOfRange, IntOutOfRange]);
+    }
+
+    fn string_value(input: &str) -> Option<LitValue> {
+        let lexed = lex(input).unwrap();
+        assert_eq!(&lexed.kinds()[..], &[Litteral(StringLit)]);
+        lexed.get_literal(0).cloned()
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            string_value(r#""a\n\tb\\c\"d\u{1F600}\u{e9}""#),
+            Some(LitValue::Str("a\n\tb\\c\"d\u{1F600}\u{e9}".into())),
+        );
+        assert_eq!(string_value(r#""""#), Some(LitValue::Str("".into())));
+        assert_eq!(string_value(r#"r"a\n\u{e9}""#), Some(LitValue::Str(r"a\n\u{e9}".into())));
+    }
+
+    #[test]
+    fn test_string_bad_escapes() {
+        let lexed = lex(r#"s = "a\qb\u{110000}c\u{}\u41""#).unwrap();
+        match_kinds(lexed.kinds(), vec![Ident, Equals, Litteral(StringLit)]);
+        assert_eq!(lexed.get_literal(2), None);
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason()).collect();
+        match reasons[..] {
+            [UnknownEscape(a), InvalidUnicodeEscape(b), InvalidUnicodeEscape(c), InvalidUnicodeEscape(d)] => {
+                assert_eq!([a.pos, b.pos, c.pos, d.pos], [6, 9, 20, 24]);
+            }
+            _ => panic!("Unexpected errors {reasons:?}"),
+        }
+    }
+
+    #[test]
+    fn test_multiline_string() {
+        let src = r#""""
+            vec4 main() \{
+                return "x";
+
+            \}
+            """"#;
+        assert_eq!(
+            string_value(src),
+            Some(LitValue::Str("vec4 main() {\n    return \"x\";\n\n}".into())),
+        );
+        let src = "r\"\"\"\n  a\\n\n    b\n  \"\"\"";
+        assert_eq!(string_value(src), Some(LitValue::Str("a\\n\n  b".into())));
+        // CRLF line breaks are dedented as LF ones.
+        let src = "\"\"\"\r\n    a\r\n      b\r\n    \"\"\"";
+        assert_eq!(string_value(src), Some(LitValue::Str("a\n  b".into())));
+        let lexed = lex("-{|\r\n  Doubles\r\n    x\r\n}-").unwrap();
+        assert_eq!(lexed.get_doc(0).as_deref(), Some("Doubles\n  x"));
+    }
+
+    fn fragment_values(lexed: &LexedBuffer) -> Vec<Option<LitValue>> {
+        (0..lexed.nb_tokens())
+            .filter(|&id| matches!(lexed.get_kind(id), StrHead | StrMiddle | StrTail))
+            .map(|id| lexed.get_literal(id).cloned())
+            .collect()
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let lexed = lex(r#"print "pos = {x}, {f { a = "\{{y}\}" }}!""#).unwrap();
+        match_kinds(lexed.kinds(), vec![
+            Ident,
+            StrHead, Ident, StrMiddle,
+            Ident, Lbrace, Ident, Equals,
+            StrHead, Ident, StrTail,
+            Rbrace, StrTail,
+        ]);
+        let str = |s: &str| Some(LitValue::Str(s.into()));
+        assert_eq!(fragment_values(&lexed), vec![
+            str("pos = "), str(", "), str("{"), str("}"), str("!"),
+        ]);
+        assert!(lexed.minor_errors.is_empty(), "{:?}", lexed.minor_errors);
+        // An interpolation needs an expression.
+        let lexed = lex(r#"print "A {}" a"#).unwrap();
+        match_kinds(lexed.kinds(), vec![Ident, StrHead, StrTail, Ident]);
+        let err = &lexed.minor_errors()[0];
+        assert_eq!((err.reason(), err.span().start.pos, err.span().end.pos), (&EmptyInterpolation, 9, 11));
+        // Raw strings don't interpolate.
+        assert_eq!(string_value(r#"r"{x}""#), str("{x}"));
+    }
+
+    #[test]
+    fn test_multiline_interpolation() {
+        let lexed = lex(r#""""
+                pos:
+                    {x},
+                  {y}
+                """"#).unwrap();
+        match_kinds(lexed.kinds(), vec![StrHead, Ident, StrMiddle, Ident, StrTail]);
+        let str = |s: &str| Some(LitValue::Str(s.into()));
+        assert_eq!(fragment_values(&lexed), vec![str("pos:\n    "), str(",\n  "), str("")]);
+    }
+
+    #[test]
+    fn test_interpolation_delims() {
+        // The paren isn't closed inside the interpolation, the one outside can't close it.
+        let lexed = lex(r#"( "{ ( }" )"#).unwrap();
+        match_kinds(lexed.kinds(), vec![Lpar, StrHead, Lpar, StrTail, Rpar]);
+        assert_eq!(error_locs(&lexed), vec![(5, Some(7))]);
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let err = lex(r#"a = "abc"#).unwrap_err();
+        assert!(matches!(err.0.reason(), UnexpectedEOI));
+        // Nor in one of its interpolations.
+        for src in [r#"a = "b {x"#, r#"a = "b {x} {f {y"#] {
+            let err = lex(src).unwrap_err();
+            assert!(matches!(err.0.reason(), UnexpectedEOI));
+            assert!(!err.1.complete);
+            assert!(matches!(Lexer::new(src).last(), Some(Err(err)) if err.reason() == &UnexpectedEOI));
+        }
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let lexed = lex(r"A 'a', B '\n', C '\'', D '\u{e9}'").unwrap();
+        let values: Vec<_> = (0..lexed.nb_tokens())
+            .filter_map(|id| lexed.get_literal(id).cloned())
+            .collect();
+        assert_eq!(values, vec![
+            LitValue::Char('a'),
+            LitValue::Char('\n'),
+            LitValue::Char('\''),
+            LitValue::Char('\u{e9}'),
+        ]);
+        assert!(lexed.minor_errors.is_empty());
+    }
+
+    #[test]
+    fn test_char_errors() {
+        let lexed = lex("a = ''\nb = 'ab'\nc = 'e\u{301}'\nd = 'x\ne = '").unwrap();
+        match_kinds(lexed.kinds(), [Ident, Equals, Litteral(Char)].repeat(5));
+        assert!((0..lexed.nb_tokens()).all(|id| lexed.get_literal(id).is_none()));
+        let reasons: Vec<_> = lexed.minor_errors.iter().map(|e| e.reason()).collect();
+        assert!(matches!(
+            reasons[..],
+            [EmptyChar, MultiCharLiteral, MultiCharLiteral, UnterminatedChar, UnterminatedChar]
+        ));
+    }
+
+    #[test]
+    fn test_operators() {
+        let lexed = lex(r#"
+            x |> add_one <| print
+            a -> b // c |- d
+            f . g .. h ._ i ... ?
+            a + b * c / d
+        "#).unwrap();
+        match_kinds(
+            lexed.kinds,
+            vec![
+                // x |> add_one <| print
+                Ident, Pipe, Ident, RevPipe, Ident,
+                // a -> b // c |- d
+                Ident, Arrow, Ident, Merge, Ident, Fork, Ident,
+                // f . g .. h ._ i ... ?
+                Ident, Dot, Ident, DotDot, Ident, DotUnderscore, Ident, Ellipsis, Question,
+                // a + b * c / d
+                Ident, Plus, Ident, Star, Ident, Slash, Ident,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_longest_match() {
+        let lexed = lex("|>>|-<||....").unwrap();
+        match_kinds(
+            lexed.kinds,
+            vec![Pipe, GT, Fork, RevPipe, Bar, Ellipsis, Dot],
+        );
+    }
+
+    #[test]
+    fn test_lossless() {
+        let src = "  add = a: b:\r\n\ta + b -- sum\n\n  \"x {a}\"  \n";
+        let lexed = lex_lossless(src).unwrap();
+        assert_eq!(lexed.lossless_text(), src);
+        let kinds = |id| lexed.leading_trivia(id).iter().map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(kinds(0), vec![TriviaKind::Whitespace]);
+        assert_eq!(kinds(1), vec![TriviaKind::Whitespace]);
+        assert_eq!(kinds(6), vec![TriviaKind::Newline, TriviaKind::Whitespace]);
+        let eof = lexed.nb_tokens();
+        assert_eq!(kinds(eof), vec![TriviaKind::Whitespace, TriviaKind::Newline]);
+        assert_eq!(lexed.get_trivia_txt(&lexed.leading_trivia(6)[0]), "\r\n".into());
+
+        let plain = lex(src).unwrap();
+        assert!(!plain.has_trivia());
+        assert!(plain.leading_trivia(0).is_empty());
+        assert_eq!(plain.kinds, lexed.kinds);
+    }
+
+    #[test]
+    fn test_lexer_stream() {
+        let src = "add = a: b: a + b\nc = \"{add 1 2}\" |> print";
+        let lexed = lex(src).unwrap();
+        let tokens: Vec<_> = Lexer::new(src).map(|t| t.unwrap()).collect();
+        assert_eq!(tokens.len(), lexed.nb_tokens());
+        for (id, token) in tokens.iter().enumerate() {
+            assert_eq!(token.kind, lexed.kinds[id]);
+            assert_eq!(token.span, lexed.spans[id]);
+            assert_eq!(token.text, &*lexed.get_token_txt(id));
+        }
+
+        // Only the tokens asked for are lexed, the error comes after them.
+        let mut lexer = Lexer::new("a |> \"unterminated");
+        assert_eq!(lexer.next().unwrap().unwrap().text, "a");
+        assert_eq!(lexer.next().unwrap().unwrap().kind, Pipe);
+        let err = lexer.next().unwrap().unwrap_err();
+        assert_eq!(err.reason(), &UnexpectedEOI);
+        assert!(lexer.next().is_none());
+
+        // The buffer holds the tokens already yielded.
+        let mut lexer = Lexer::new(src);
+        lexer.next();
+        assert_eq!(lexer.lex().unwrap(), lexed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Configured a lexer after lexing.")]
+    fn test_configure_after_lexing() {
+        let mut lexer = Lexer::new("a = 1");
+        lexer.next();
+        let _ = lexer.lossless();
+    }
+
+    #[test]
+    fn test_layout() {
+        let src = "main =\n    x = 1\n  -- aside\n    match x with\n        A -> a\n        B -> b\n    |> print\n--| The y.\ny = (a\n  b)\n";
+        let lexed = Lexer::new(src).with_layout().lex().unwrap();
+        match_kinds(
+            lexed.kinds(),
+            vec![
+                Ident, Equals,
+                Indent, Ident, Equals, Litteral(Int(None)),
+                Comment,
+                Newline, KeyWord(Match), Ident, KeyWord(With),
+                Indent, Ident, Arrow, Ident,
+                Newline, Ident, Arrow, Ident,
+                Dedent, Newline, Pipe, Ident,
+                DocComment,
+                Dedent, Newline, Ident, Equals, Lpar, Ident,
+                Ident, Rpar,
+            ],
+        );
+        assert_eq!(lexed.doc_of(26), Some("The y.".into()));
+        assert!(lexed.minor_errors().is_empty());
+
+        // Blocks still open at the end are closed.
+        let lexed = Lexer::new("f =\n  g").with_layout().lex().unwrap();
+        match_kinds(lexed.kinds(), vec![Ident, Equals, Indent, Ident, Dedent]);
+
+        let lexed = Lexer::new("a\n    b\n  c").with_layout().lex().unwrap();
+        match_kinds(lexed.kinds(), vec![Ident, Indent, Ident, Dedent, Newline, Ident]);
+        let errors: Vec<_> = lexed.minor_errors().iter().map(|e| e.reason.clone()).collect();
+        assert_eq!(errors, vec![InconsistentDedent]);
+
+        // Only the first token of a line which isn't a comment starts the line, a `-`
+        // may start either.
+        let lexed = Lexer::new("f =\n  -{ a }-\n  -x\n  -- b\n  - y").with_layout().lex().unwrap();
+        match_kinds(
+            lexed.kinds(),
+            vec![Ident, Equals, Comment, Indent, Dash, Ident, Comment, Newline, Dash, Ident, Dedent],
+        );
+
+        // Tabs have no width in columns.
+        let lexed = Lexer::new("f =\n\tg\n  \th").with_layout().lex().unwrap();
+        let errors: Vec<_> = lexed.minor_errors().iter().map(|e| e.reason.clone()).collect();
+        assert_eq!(errors, vec![TabIndent, TabIndent]);
+
+        // The layout tokens have no text.
+        let lexed = Lexer::new(src).lossless().with_layout().lex().unwrap();
+        assert_eq!(lexed.lossless_text(), src);
+    }
+
+    #[test]
+    fn test_non_ascii_spans() {
+        let src = "héllo = \"ü\" --ß\n  wörld";
+        let lexed = lex(src).unwrap();
+        let texts: Vec<_> = (0..lexed.nb_tokens()).map(|id| lexed.get_token_txt(id)).collect();
+        assert_eq!(texts, ["héllo", "=", "\"ü\"", "--ß", "wörld"].map(IStr::from));
+        let world = lexed.spans[4];
+        assert_eq!((world.start.pos, world.start.row, world.start.col), (21, 1, 2));
+        assert_eq!((world.end.pos, world.end.row, world.end.col), (27, 1, 7));
+        assert_eq!(Lexer::new(src).lossless().lex().unwrap().lossless_text(), src);
+    }
+
+    #[test]
+    fn test_unicode_idents() {
+        let lexed = lex("café cafe\u{301} λ 日本 _x1 a€b").unwrap();
+        match_kinds(lexed.kinds(), vec![Ident, Ident, Ident, Ident, Ident, Ident, Unrecognized, Ident]);
+        assert_eq!(lexed.get_ident(0), lexed.get_ident(1));
+        assert_ne!(lexed.get_token_txt(0), lexed.get_token_txt(1));
+        assert_eq!(lexed.get_ident(4), Some("_x1"));
+        assert_eq!(lexed.get_ident(6), None);
+        assert_eq!(lexed.get_symbol(0), lexed.get_symbol(1));
+        assert_eq!(lexed.get_symbol(0), Some(Symbol::intern("café")));
+        assert_eq!(lexed.get_symbol(6), None);
+        assert!(lexed.minor_errors().is_empty());
+
+        // Cyrillic `а` and fullwidth `ｘ`.
+        let lexed = lex("p\u{430}th \u{430}\u{441} \u{ff58}").unwrap();
+        let reasons: Vec<_> = lexed.minor_errors().iter().map(|e| e.reason().clone()).collect();
+        assert_eq!(reasons, vec![MixedScriptIdent, ConfusableIdent("ac".into()), ConfusableIdent("x".into())]);
+        assert!(reasons.iter().all(Reason::is_warning));
+        match_kinds(lexed.kinds(), vec![Ident, Ident, Ident]);
+    }
 }
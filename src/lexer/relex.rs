@@ -0,0 +1,291 @@
+use std::ops::Range;
+
+use crate::utils::*;
+
+use super::{lex, lex_lossless, lex_token, negates_next, Lexer, token::*, DelimStack, LexError, LexMode, LexedBuffer, LexerState, Result, TkHandle};
+
+impl LexedBuffer {
+    /// Lex the source with the bytes in `range` replaced by `replacement`, the range must
+    /// lie on char boundaries. Only the tokens around the edit are lexed again, the others
+    /// are reused and shifted. The result is the same as lexing the whole edited source.
+    pub fn relex(&self, range: Range<usize>, replacement: &str) -> Result<LexedBuffer> {
+        let source = format!(
+            "{}{}{}",
+            &self.source[..range.start],
+            replacement,
+            &self.source[range.end..]
+        );
+        if !self.complete {
+            return self.configure(Lexer::new(&source)).lex();
+        }
+
+        let restart_tk = self.restart_token(range.start);
+        let restart = match restart_tk {
+            0 => Loc::default(),
+            r => self.spans[r - 1].end,
+        };
+        let edit_start = restart.advanced(&self.source[restart.pos..range.start]);
+        let old_end = edit_start.advanced(&self.source[range]);
+        let new_end = edit_start.advanced(replacement);
+        // Maps a loc of the old source outside of the edit to the new source.
+        let shift = |loc: Loc| {
+            if loc.pos >= old_end.pos {
+                Some(loc.shifted(old_end, new_end))
+            } else if loc.pos < edit_start.pos {
+                Some(loc)
+            } else {
+                None
+            }
+        };
+
+        let mut state = LexerState::new_at(&source, restart);
+        state.file = self.file;
+        if self.has_trivia() {
+            state = state.keep_trivia();
+        }
+        if self.layout {
+            state = state.with_layout();
+        }
+        self.copy_prefix(&mut state, restart_tk);
+        let mut old_stack = state.delim_stack.clone();
+        let mut old_indents = state.indents.clone();
+        let mut old_tk = restart_tk;
+
+        state.trim_whitespaces();
+        loop {
+            // Once past the edit, look for an old token starting at the same place with the
+            // same lexer state, the rest of the old tokens can then be reused.
+            if state.start.pos >= new_end.pos && matches!(state.modes[..], [LexMode::Default]) {
+                let old_pos = state.start.pos - new_end.pos + old_end.pos;
+                while old_tk < self.kinds.len() && self.spans[old_tk].start.pos < old_pos {
+                    old_stack.balance(self.kinds[old_tk], self.spans[old_tk], |_| ());
+                    if state.layout {
+                        replay_layout(&mut old_indents, self.kinds[old_tk], self.spans[old_tk]);
+                    }
+                    old_tk += 1;
+                }
+                // The layout tokens before the first token of a line depend on its column,
+                // it is only reused once a token is lexed on the line.
+                let synced = old_tk < self.kinds.len()
+                    && self.spans[old_tk].start.pos == old_pos
+                    && !self.kinds[old_tk].is_layout()
+                    && !(state.layout && state.line_start)
+                    // The range of a number depends on a `-` before it and on what the `-` follows.
+                    && negates_next(&self.kinds[..old_tk]) == state.negated()
+                    && after_operand(&self.kinds[..old_tk]) == after_operand(&state.tokens)
+                    && old_indents == state.indents
+                    && old_stack.0.len() == state.delim_stack.0.len()
+                    && old_stack.0.iter().zip(&state.delim_stack.0).all(|((ok, os), (nk, ns))| {
+                        ok == nk && shift(os.start) == Some(ns.start) && shift(os.end) == Some(ns.end)
+                    });
+                if synced {
+                    return Ok(self.splice(state, old_tk, |loc| shift(loc).unwrap_or(loc)));
+                }
+            }
+            let Some(c) = state.input.next() else { break };
+            if let Err(err) = lex_token(&mut state, c) {
+                return Err(state.stop(err));
+            }
+            state.trim_whitespaces();
+        }
+        state.finish()
+    }
+
+    /// Set up `lexer` with the options this buffer was lexed with.
+    fn configure<'a>(&self, mut lexer: Lexer<'a>) -> Lexer<'a> {
+        lexer = lexer.in_file(self.file);
+        if self.has_trivia() {
+            lexer = lexer.lossless();
+        }
+        if self.layout {
+            lexer = lexer.with_layout();
+        }
+        lexer
+    }
+
+    /// The first token to lex again for an edit starting at the byte `pos`: the first one
+    /// ending at or after it, or the string holding it when it is in an interpolation.
+    /// The layout tokens before it are lexed again too.
+    fn restart_token(&self, pos: usize) -> TkHandle {
+        let first = self.spans.partition_point(|s| s.end.pos < pos);
+        let mut heads = vec![];
+        for (h, kind) in self.kinds[..first].iter().enumerate() {
+            match kind {
+                StrHead => heads.push(h),
+                StrTail => {
+                    heads.pop();
+                }
+                _ => (),
+            }
+        }
+        let mut restart = heads.first().copied().unwrap_or(first);
+        while restart > 0 && self.kinds[restart - 1].is_layout() {
+            restart -= 1;
+        }
+        restart
+    }
+
+    /// Fill `state` as if it just lexed the tokens before `end`.
+    fn copy_prefix(&self, state: &mut LexerState, end: TkHandle) {
+        state.tokens.extend_from_slice(&self.kinds[..end]);
+        state.spans.extend_from_slice(&self.spans[..end]);
+        state.literals.extend(self.literals.iter().filter(|(h, _)| *h < end).cloned());
+        state.docs.extend(self.docs.iter().filter(|(h, _)| *h < end).cloned());
+        state.symbols.extend_from_slice(&self.symbols[..end]);
+        state.minor_errors.extend(self.minor_errors.iter().filter(|e| e.token < end).cloned());
+        for h in 0..end {
+            state.delim_stack.balance(self.kinds[h], self.spans[h], |_| ());
+            if state.layout {
+                replay_layout(&mut state.indents, self.kinds[h], self.spans[h]);
+            }
+        }
+        // Only the comments since the last token can be on a new line.
+        let last = (0..end).rev().find(|&h| is_layout_trigger(self.kinds[h]));
+        state.line_start = last.is_none_or(|last| {
+            (last + 1..end).any(|h| self.spans[h].start.row != self.spans[h - 1].end.row)
+        });
+        if self.has_trivia() {
+            state.trivia.extend_from_slice(&self.trivia[..self.leading_trivia[end]]);
+            state.leading_trivia.extend_from_slice(&self.leading_trivia[..end]);
+            state.trivia_mark = state.trivia.len();
+        }
+    }
+
+    /// Append the old tokens from `from` to the ones lexed by `state`.
+    fn splice(&self, mut state: LexerState, from: TkHandle, shift: impl Fn(Loc) -> Loc) -> LexedBuffer {
+        let to = state.tokens.len();
+        let handle = |h: TkHandle| h - from + to;
+        let shift_span = |s: &Span| Span::new(s.file, shift(s.start), shift(s.end));
+        state.tokens.extend_from_slice(&self.kinds[from..]);
+        state.spans.extend(self.spans[from..].iter().map(shift_span));
+        state.literals.extend(
+            self.literals
+                .iter()
+                .filter(|(h, _)| *h >= from)
+                .map(|(h, v)| (handle(*h), v.clone())),
+        );
+        state.docs.extend(
+            self.docs
+                .iter()
+                .filter(|(h, _)| *h >= from)
+                .map(|(h, d)| (handle(*h), d.clone())),
+        );
+        state.symbols.extend_from_slice(&self.symbols[from..]);
+        state.minor_errors.extend(
+            self.minor_errors
+                .iter()
+                .filter(|e| e.token >= from)
+                .map(|e| LexError {
+                    span: shift_span(&e.span),
+                    reason: e.reason.clone().map_locs(&shift),
+                    token: handle(e.token),
+                }),
+        );
+        if self.has_trivia() {
+            // The leading trivia of `from` was just lexed again, the following ones are reused.
+            let first = self.leading_trivia[from + 1];
+            let base = state.trivia.len();
+            state.leading_trivia.push(state.trivia_mark);
+            state.leading_trivia.extend(
+                self.leading_trivia[from + 1..self.kinds.len()]
+                    .iter()
+                    .map(|t| t - first + base),
+            );
+            state.trivia.extend(
+                self.trivia[first..]
+                    .iter()
+                    .map(|t| Trivia { kind: t.kind, span: shift_span(&t.span) }),
+            );
+            state.trivia_mark = self.leading_trivia[self.kinds.len()] - first + base;
+        }
+        state.finalize()
+    }
+}
+
+/// Whether the last of `kinds` ends an operand, a `-` after it is then a subtraction.
+fn after_operand(kinds: &[TkKind]) -> bool {
+    kinds.iter().rev().find(|k| !matches!(k, Comment | DocComment)).is_some_and(TkKind::ends_operand)
+}
+
+/// Whether a token can be the first of a line for the layout.
+fn is_layout_trigger(kind: TkKind) -> bool {
+    !matches!(kind, Comment | DocComment) && !kind.is_layout()
+}
+
+/// Update the columns of the enclosing blocks as the token was lexed.
+fn replay_layout(indents: &mut Vec<usize>, kind: TkKind, span: Span) {
+    match kind {
+        Indent => indents.push(span.start.col),
+        Dedent => {
+            indents.pop();
+        }
+        kind if indents.is_empty() && is_layout_trigger(kind) => indents.push(span.start.col),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn relex_matches(source: &str, range: Range<usize>, replacement: &str) {
+        let mut edited = source.to_string();
+        edited.replace_range(range.clone(), replacement);
+        let lex_layout = |s: &str| Lexer::new(s).with_layout().lex();
+        let lex_all = |s: &str| Lexer::new(s).lossless().with_layout().lex();
+        for lex in [lex, lex_lossless, lex_layout, lex_all] {
+            let lexed = lex(source).unwrap_or_else(|err| err.1);
+            let relexed = lexed.relex(range.clone(), replacement);
+            if let Ok(relexed) = &relexed {
+                if relexed.has_trivia() {
+                    assert_eq!(relexed.lossless_text(), edited);
+                }
+            }
+            assert_eq!(relexed, lex(&edited));
+        }
+    }
+
+    #[test]
+    fn test_relex_edits() {
+        let src = "add = a: b: a + b\n-- comment\nc = \"pos = {x}, {y}\" |> print";
+        // Rename an identifier.
+        relex_matches(src, 0..3, "plus");
+        // Open a string swallowing the rest of the line.
+        relex_matches(src, 4..5, "\"");
+        // Edit inside an interpolation.
+        relex_matches(src, 41..42, "(z)");
+        // Turn the comment into a doc comment.
+        relex_matches(src, 20..20, "|");
+        // Unbalance delimiters.
+        relex_matches(src, 6..6, "( [");
+        // A `-` changes the range of the number after it.
+        relex_matches("a = -128i8", 4..5, "");
+        relex_matches("a = 128i8", 4..4, "-");
+        relex_matches("a = b - 128i8", 4..5, "+");
+        // Append at the end.
+        relex_matches(src, src.len()..src.len(), " }");
+    }
+
+    fn fragment() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "a", "bc", "if", " ", "\n", "  ", "(", ")", "{", "}", "[", "]", "|", ">", "<",
+            "-", "--", "-{", "}-", "\"", "'", "\\", "0x", "1", "2.5", "e", ".", "_", "r",
+            "é", "e\u{301}", "\u{430}", "\"{", "}\"", ":", "=", "128i8", "\t",
+        ])
+    }
+
+    fn text() -> impl Strategy<Value = String> {
+        prop::collection::vec(fragment(), 0..40).prop_map(|f| f.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_relex_is_full_lex(source in text(), a in any::<prop::sample::Index>(), b in any::<prop::sample::Index>(), replacement in text()) {
+            let bounds: Vec<usize> = source.char_indices().map(|(i, _)| i).chain([source.len()]).collect();
+            let (a, b) = (a.index(bounds.len()), b.index(bounds.len()));
+            let range = bounds[a.min(b)]..bounds[a.max(b)];
+            relex_matches(&source, range, &replacement);
+        }
+    }
+}
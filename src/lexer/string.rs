@@ -0,0 +1,270 @@
+use crate::utils::*;
+
+use super::{cursor::Cursor, token::*, LexMode, LexerState, Reason, Step, TkHandle};
+use Reason::*;
+
+/// A string being lexed. It is kept in the lexer modes while one of its interpolations
+/// is lexed.
+#[derive(Debug)]
+pub struct StringState {
+    raw: bool,
+    multiline: bool,
+    valid: bool,
+    text: StringBuilder,
+    /// The head and middle fragments already pushed.
+    fragments: Vec<TkHandle>,
+}
+
+/// Lex a string after its opening `"`. Handles `"..."`, raw strings `r"..."` and
+/// multi-line strings `"""..."""` whose common indentation is stripped.
+/// Non raw strings can embed expressions with `"pos = {x}, {y}"`.
+pub(super) fn lex_string(state: &mut LexerState, raw: bool) -> Step {
+    let multiline = if state.input.next_is('"') {
+        if !state.input.next_is('"') {
+            state.push_literal(StringLit, LitValue::Str("".into()));
+            return Ok(());
+        }
+        true
+    } else {
+        false
+    };
+    let string = StringState {
+        raw,
+        multiline,
+        valid: true,
+        text: StringBuilder::default(),
+        fragments: vec![],
+    };
+    lex_string_body(state, string)
+}
+
+/// Resume lexing a string after the `}` closing one of its interpolations.
+pub(super) fn resume_string(state: &mut LexerState) -> Step {
+    let Some(LexMode::Interpolation(string)) = state.modes.pop() else {
+        unreachable!("Resumed a string outside of an interpolation.")
+    };
+    let last = state.tokens.iter().rposition(|k| !matches!(k, Comment | DocComment));
+    if let Some(fragment) = last.filter(|&h| matches!(state.tokens[h], StrHead | StrMiddle)) {
+        // The fragment ends with the `{`, a single byte.
+        let end = state.spans[fragment].end;
+        let open = Loc { pos: end.pos - 1, col: end.col - 1, ..end };
+        let span = Span::new(state.file, open, state.input.loc());
+        state.record_err(span, EmptyInterpolation);
+    }
+    lex_string_body(state, string)
+}
+
+fn lex_string_body(state: &mut LexerState, mut string: StringState) -> Step {
+    let interpolates = loop {
+        let loc = state.input.loc();
+        match state.input.next() {
+            None => return Err(state.error(UnexpectedEOI)),
+            Some('"') if !string.multiline => break false,
+            Some('"') => {
+                if state.input.next_is('"') {
+                    if state.input.next_is('"') {
+                        break false;
+                    }
+                    string.text.push('"');
+                }
+                string.text.push('"');
+            }
+            Some('{') if !string.raw => break true,
+            Some('\\') if !string.raw => match lex_escape(&mut state.input, loc) {
+                Ok(c) => string.text.push_escaped(c),
+                Err(reason) => {
+                    string.valid = false;
+                    state.push_err(reason);
+                }
+            },
+            Some('\n') if string.multiline => string.text.new_line(),
+            // A `\r\n` line break is read as a `\n`.
+            Some('\r') if string.multiline && state.input.next_is('\n') => string.text.new_line(),
+            Some(c) => string.text.push(c),
+        }
+    };
+
+    if interpolates {
+        // Switch to the interpolation mode until the matching `}`.
+        string.text.cut();
+        let kind = if string.fragments.is_empty() { StrHead } else { StrMiddle };
+        string.fragments.push(state.tokens.len());
+        state.push_token(kind);
+        state.modes.push(LexMode::Interpolation(string));
+        return Ok(());
+    }
+
+    if string.fragments.is_empty() {
+        if string.valid {
+            let value = string.text.finish(string.multiline).concat();
+            state.push_literal(StringLit, LitValue::Str(value.into()))
+        } else {
+            state.push_token(Litteral(StringLit))
+        }
+        return Ok(());
+    }
+
+    string.fragments.push(state.tokens.len());
+    state.push_token(StrTail);
+    if string.valid {
+        let values = string.text.finish(string.multiline);
+        for (handle, value) in string.fragments.into_iter().zip(values) {
+            state.record_literal(handle, LitValue::Str(value.into()));
+        }
+    }
+    Ok(())
+}
+
+/// Lex a char after its opening `'`. The litteral stops at the end of the line if it
+/// isn't closed.
+pub(super) fn lex_char(state: &mut LexerState) {
+    let mut chars = vec![];
+    let mut valid = true;
+    let mut closed = false;
+    loop {
+        let loc = state.input.loc();
+        match state.input.next() {
+            None | Some('\n') => break,
+            Some('\'') => {
+                closed = true;
+                break;
+            }
+            Some('\\') => match lex_escape(&mut state.input, loc) {
+                Ok(c) => chars.push(c),
+                Err(reason) => {
+                    valid = false;
+                    state.push_err(reason);
+                }
+            },
+            Some(c) => chars.push(c),
+        }
+    }
+    let value = match chars[..] {
+        _ if !closed => Err(UnterminatedChar),
+        [] if valid => Err(EmptyChar),
+        [c] if valid => Ok(LitValue::Char(c)),
+        _ if valid => Err(MultiCharLiteral),
+        _ => return state.push_token(Litteral(Char)),
+    };
+    state.push_literal_or_err(Char, value)
+}
+
+/// Decode an escape sequence after its `\`, `loc` being the location of the `\`.
+fn lex_escape(input: &mut Cursor, loc: Loc) -> std::result::Result<char, Reason> {
+    let c = match input.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('{') => '{',
+        Some('}') => '}',
+        Some('u') => {
+            if !input.next_is('{') {
+                return Err(InvalidUnicodeEscape(loc));
+            }
+            let mut digits = String::new();
+            while let Some(d) = input.next_if(char::is_ascii_hexdigit) {
+                digits.push(d);
+            }
+            if !input.next_is('}') || digits.is_empty() || digits.len() > 6 {
+                return Err(InvalidUnicodeEscape(loc));
+            }
+            u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(InvalidUnicodeEscape(loc))?
+        }
+        _ => return Err(UnknownEscape(loc)),
+    };
+    Ok(c)
+}
+
+/// Accumulates the text of a string litteral or doc comment line by line, remembering the
+/// indentation of each line so multi-line text can be dedented. The text can be cut in
+/// several fragments around interpolations.
+#[derive(Debug, Default)]
+pub(super) struct StringBuilder {
+    done: Vec<Line>,
+    line: Line,
+}
+
+#[derive(Debug, Default)]
+struct Line {
+    text: String,
+    /// Number of leading whitespaces, `None` while the line is blank.
+    indent: Option<usize>,
+    /// Byte offsets where the text is cut in fragments.
+    cuts: Vec<usize>,
+}
+
+impl StringBuilder {
+    pub(super) fn push(&mut self, c: char) {
+        if c != ' ' && c != '\t' {
+            self.mark_content();
+        }
+        self.line.text.push(c);
+    }
+
+    /// Push a char coming from an escape sequence, it never counts as indentation.
+    pub(super) fn push_escaped(&mut self, c: char) {
+        self.mark_content();
+        self.line.text.push(c);
+    }
+
+    /// Start a new fragment, an interpolation counts as content of the line.
+    pub(super) fn cut(&mut self) {
+        self.mark_content();
+        self.line.cuts.push(self.line.text.len());
+    }
+
+    pub(super) fn new_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.done.push(line);
+    }
+
+    fn mark_content(&mut self) {
+        if self.line.indent.is_none() {
+            self.line.indent = Some(self.line.text.len());
+        }
+    }
+
+    /// Join the lines and split them in fragments at the cuts. When `dedent` is set the
+    /// common indentation is removed and blank first and last lines, the ones holding the
+    /// opening and closing quotes, are dropped.
+    pub(super) fn finish(mut self, dedent: bool) -> Vec<String> {
+        self.new_line();
+        let mut lines = &self.done[..];
+        let mut common = 0;
+        if dedent {
+            if let [Line { indent: None, .. }, rest @ ..] = lines {
+                lines = rest;
+            }
+            if let [rest @ .., Line { indent: None, .. }] = lines {
+                lines = rest;
+            }
+            common = lines.iter().filter_map(|l| l.indent).min().unwrap_or(0);
+        }
+        let mut fragments = vec![String::new()];
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                fragments.last_mut().unwrap().push('\n');
+            }
+            // Indentation is made of single byte chars.
+            let mut pos = match line.indent {
+                Some(_) => common,
+                None if dedent => line.text.len(),
+                None => 0,
+            };
+            for &cut in &line.cuts {
+                fragments.last_mut().unwrap().push_str(&line.text[pos..cut]);
+                fragments.push(String::new());
+                pos = cut;
+            }
+            fragments.last_mut().unwrap().push_str(&line.text[pos..]);
+        }
+        fragments
+    }
+}
@@ -14,6 +14,7 @@ pub enum TkKind {
     Colon,    // :
     Equals,   // =
     Comma,    // ,
+    Semicolon, // ;
     At,       // @
     Dollar,   // $
     Dash,     // -
@@ -27,26 +28,54 @@ pub enum TkKind {
     Lbracket, // [
     Rbracket, // ]
     Bar,      // |
+    Plus,     // +
+    Star,     // *
+    Slash,    // /
+    Question, // ?
+
+    // Multi-character operators.
+    Pipe,          // |>
+    RevPipe,       // <|
+    Fork,          // |-
+    Merge,         // //
+    Arrow,         // ->
+    DotDot,        // ..
+    DotUnderscore, // ._
+    Ellipsis,      // ...
     Litteral(Lit), // litteral
 
+    // Interpolated strings.
+    StrHead,       // "...{
+    StrMiddle,     // }...{
+    StrTail,       // }..."
+
+    // Layout tokens, zero width and only emitted by the offside rule.
+    Indent,  // A line indented more than the block.
+    Dedent,  // The end of an indented block.
+    Newline, // A line at the indentation of the block.
+
     // Unimportant Tokens
     Comment,      // A comment.
+    DocComment,   // A documentation comment.
     Unrecognized, // None of the above.
 }
 
 impl TkKind {
     pub fn is_close_delim(&self) -> bool {
-        match self {
-            Self::Rpar | Self::Rbracket | Self::Rbrace => true,
-            _ => false,
-        }
+        matches!(self, Self::Rpar | Self::Rbracket | Self::Rbrace | Self::StrMiddle | Self::StrTail)
     }
 
     pub fn is_open_delim(&self) -> bool {
-        match self {
-            Self::Lpar | Self::Lbracket | Self::Rbrace => true,
-            _ => false,
-        }
+        matches!(self, Self::Lpar | Self::Lbracket | Self::Lbrace | Self::StrHead | Self::StrMiddle)
+    }
+
+    /// Whether the token can end an operand, a `-` after it is a subtraction.
+    pub fn ends_operand(&self) -> bool {
+        matches!(self, Self::Ident | Self::Litteral(_) | Self::Rpar | Self::Rbracket | Self::Rbrace | Self::StrTail)
+    }
+
+    pub fn is_layout(&self) -> bool {
+        matches!(self, Self::Indent | Self::Dedent | Self::Newline)
     }
 
     pub fn get_matching_delim(&self) -> Self {
@@ -58,6 +87,8 @@ impl TkKind {
             Self::Rbracket => Self::Lbracket,
             Self::Lbracket => Self::Rbracket,
             Self::Bar => Self::Bar,
+            Self::StrHead => Self::StrTail,
+            Self::StrMiddle | Self::StrTail => Self::StrHead,
             _ => unreachable!("Caled get_matching_delim on a token that wasn't a delimiter.")
         }
     }
@@ -81,12 +112,32 @@ impl fmt::Display for TkKind {
             Lbracket => write!(f, "["),
             Rbracket => write!(f, "]"),
             Comma    => write!(f, ","),
+            Semicolon => write!(f, ";"),
             Dot      => write!(f, "."),
             Bar      => write!(f, "|"),
+            Plus     => write!(f, "+"),
+            Star     => write!(f, "*"),
+            Slash    => write!(f, "/"),
+            Question => write!(f, "?"),
+            Pipe          => write!(f, "|>"),
+            RevPipe       => write!(f, "<|"),
+            Fork          => write!(f, "|-"),
+            Merge         => write!(f, "//"),
+            Arrow         => write!(f, "->"),
+            DotDot        => write!(f, ".."),
+            DotUnderscore => write!(f, "._"),
+            Ellipsis      => write!(f, "..."),
             Ident         => write!(f, "identifier"),
             Litteral(lit) => write!(f, "Lit({lit})"),
+            StrHead       => write!(f, "string head"),
+            StrMiddle     => write!(f, "string middle"),
+            StrTail       => write!(f, "string tail"),
+            Indent        => write!(f, "indent"),
+            Dedent        => write!(f, "dedent"),
+            Newline       => write!(f, "new line"),
             KeyWord(kw)   => write!(f, "Kw({kw})"),
             Comment       => write!(f, "comment"),
+            DocComment    => write!(f, "doc comment"),
             Unrecognized  => write!(f, "unrecognized"),
         }
     }
@@ -134,18 +185,126 @@ impl fmt::Display for Kw {
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Lit {
-    Int,
-    Float,
+    /// The type is only known when the litteral has a suffix, as in `2u32`.
+    Int(Option<IntTy>),
+    Float(Option<FloatTy>),
     StringLit,
+    Char,
+}
+
+/// The integer type given by a litteral suffix.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct IntTy {
+    pub signed: bool,
+    pub bits: u8,
+}
+
+/// The float type given by a litteral suffix.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct FloatTy {
+    pub bits: u8,
+}
+
+impl Lit {
+    /// The kind of a numeric litteral with the `suffix`, `None` if it isn't a scalar type.
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        let (ty, bits) = suffix.split_at_checked(1)?;
+        let bits = match bits {
+            "8" => 8,
+            "16" => 16,
+            "32" => 32,
+            "64" => 64,
+            _ => return None,
+        };
+        match ty {
+            "i" => Some(Int(Some(IntTy { signed: true, bits }))),
+            "u" => Some(Int(Some(IntTy { signed: false, bits }))),
+            "f" if bits >= 16 => Some(Float(Some(FloatTy { bits }))),
+            _ => None,
+        }
+    }
+}
+
+impl IntTy {
+    /// Whether `value` can be represented by this type, or its opposite when `negated`.
+    pub fn holds(&self, value: u64, negated: bool) -> bool {
+        let max = u64::MAX >> (64 - self.bits as u32 + self.signed as u32);
+        value <= max || (self.signed && negated && value == max + 1)
+    }
+}
+
+impl FloatTy {
+    /// Whether `value` rounds to a finite number of this type.
+    pub fn holds(&self, value: f64) -> bool {
+        match self.bits {
+            // Values from the largest half plus half an ulp round to infinity.
+            16 => value.abs() < 65520.0,
+            32 => (value as f32).is_finite(),
+            _ => value.is_finite(),
+        }
+    }
+}
+
+/// The decoded value of a litteral token.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LitValue {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(IStr),
+    Char(char),
+}
+
+/// The kind of the text skipped between tokens.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriviaKind {
+    /// A run of whitespaces on a single line.
+    Whitespace,
+    /// A `\n` or `\r\n`.
+    Newline,
+}
+
+/// Text skipped between tokens, only recorded by the lossless lexer.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LitValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LitValue::Int(v) => write!(f, "{v}"),
+            LitValue::UInt(v) => write!(f, "{v}"),
+            LitValue::Float(v) => write!(f, "{v}"),
+            LitValue::Str(v) => write!(f, "{v:?}"),
+            LitValue::Char(v) => write!(f, "{v:?}"),
+        }
+    }
+}
+
+impl fmt::Display for IntTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+impl fmt::Display for FloatTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "f{}", self.bits)
+    }
 }
 
 impl fmt::Display for Lit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Lit::*;
         match self {
-            Int => write!(f, "int"),
-            Float => write!(f, "float"),
+            Int(None) => write!(f, "int"),
+            Int(Some(ty)) => write!(f, "{ty}"),
+            Float(None) => write!(f, "float"),
+            Float(Some(ty)) => write!(f, "{ty}"),
             StringLit => write!(f, "string"),
+            Char => write!(f, "char"),
         }
     }
 }
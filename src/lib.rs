@@ -1,34 +1,72 @@
 #[allow(unused)]
-//pub mod parser;
+pub mod parser;
+#[allow(unused)]
 pub mod lexer;
-mod input;
+pub mod lines;
+pub mod diagnostic;
+pub mod source_map;
+pub mod symbol;
 //pub mod types;
 
+pub use utils::{Loc, Span};
+
 mod utils {
     use std::fmt;
     use std::ops::Range;
 
-    use crate::input::{MetaData, InputMetaData};
+    pub use crate::source_map::FileId;
 
     pub type IStr = std::rc::Rc<str>;
     pub type IVec<T> = std::rc::Rc<[T]>;
 
+    /// A position in the source, `row` and `col` start at 0 and `col` counts chars.
     #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Loc {
-        pub(crate) pos: usize,
+        /// The byte offset in the source.
+        pub pos: usize,
         pub row: usize,
         pub col: usize,
     }
 
+    impl Loc {
+        /// Move a loc located after `from` as if `from` was moved to `to`.
+        pub(crate) fn shifted(self, from: Loc, to: Loc) -> Loc {
+            let col = if self.row == from.row { self.col - from.col + to.col } else { self.col };
+            Loc {
+                pos: self.pos - from.pos + to.pos,
+                row: self.row - from.row + to.row,
+                col,
+            }
+        }
+
+        /// The loc reached after reading `text` from this one.
+        pub(crate) fn advanced(mut self, text: &str) -> Loc {
+            text.chars().for_each(|c| self.update(c));
+            self
+        }
+
+        /// Move past `c`.
+        pub(crate) fn update(&mut self, c: char) {
+            if c == '\n' {
+                self.row += 1;
+                self.col = 0;
+            } else {
+                self.col += 1;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
     #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Span {
+        pub file: FileId,
         pub start: Loc,
         pub end: Loc,
     }
 
     impl Span {
-        pub fn new(start: Loc, end: Loc) -> Self {
-            Self { start, end }
+        pub fn new(file: FileId, start: Loc, end: Loc) -> Self {
+            Self { file, start, end }
         }
 
         pub fn is_overlaping(&self, other: Self) -> bool {
@@ -40,23 +78,23 @@ mod utils {
         pub fn around(a: Self, b: Self) -> Self {
             let start = if a.start.pos < b.start.pos { a } else { b }.start;
             let end = if a.end.pos < b.end.pos { b } else { a }.end;
-            Self::new(start, end)
+            Self::new(a.file, start, end)
         }
 
         pub fn inside(a: Self, b: Self) -> Option<Self> {
             if a.is_overlaping(b) {
-                return None;
+                None
             } else {
                 let start = if a.end.pos < b.end.pos { a } else { b }.end;
                 let end = if a.start.pos < b.start.pos { b } else { a }.start;
-                Some(Self::new(start, end))
+                Some(Self::new(a.file, start, end))
             }
         }
     }
 
-    impl Into<Range<usize>> for Span {
-        fn into(self) -> Range<usize> {
-            self.start.pos..self.end.pos
+    impl From<Span> for Range<usize> {
+        fn from(span: Span) -> Self {
+            span.start.pos..span.end.pos
         }
     }
 
@@ -66,31 +104,10 @@ mod utils {
         }
     }
 
+    /// The 1-based line and column, as in the diagnostics.
     impl fmt::Display for Loc {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "({}, {})", self.row, self.col)
-        }
-    }
-
-    impl MetaData for Loc {
-        type InputMetaData = Self;
-
-        fn get(metadata: &Self::InputMetaData) -> &Self {
-            &metadata
-        }
-    }
-
-    impl InputMetaData for Loc {
-        type Item = char;
-
-        fn update(&mut self, c: &Self::Item) {
-            if let '\n' = c {
-                self.col += 1;
-                self.row = 0;
-            } else {
-                self.row += 1;
-            }
-            self.pos += 1;
+            write!(f, "{}:{}", self.row + 1, self.col + 1)
         }
     }
 }
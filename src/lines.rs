@@ -0,0 +1,113 @@
+use crate::utils::*;
+
+/// The unit columns are counted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    /// The one used by most editors and the language server protocol.
+    Utf16,
+    /// Chars.
+    Utf32,
+}
+
+/// A 1-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// The byte offset of the start of each line of a source, to convert byte offsets to
+/// lines and columns.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    source: IStr,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: IStr) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { source, line_starts }
+    }
+
+    pub fn nb_lines(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of the 1-based `line` without its line break.
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).map_or(self.source.len(), |next| next - 1);
+        self.source[start..end].strip_suffix('\r').unwrap_or(&self.source[start..end])
+    }
+
+    /// The line and column of the byte `offset`, which must lie on a char boundary.
+    pub fn line_col(&self, offset: usize, encoding: Encoding) -> LineCol {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let text = &self.source[self.line_starts[line]..offset];
+        let col = match encoding {
+            Encoding::Utf8 => text.len(),
+            Encoding::Utf16 => text.encode_utf16().count(),
+            Encoding::Utf32 => text.chars().count(),
+        };
+        LineCol { line: line + 1, col: col + 1 }
+    }
+
+    /// The byte offset of a line and column, `None` if it is past the end of the line or
+    /// inside a char.
+    pub fn offset(&self, pos: LineCol, encoding: Encoding) -> Option<usize> {
+        let start = *self.line_starts.get(pos.line.checked_sub(1)?)?;
+        let end = self.line_starts.get(pos.line).map_or(self.source.len(), |next| next - 1);
+        let line = &self.source[start..end];
+        let mut units = 0;
+        // The end of the line is a valid position too.
+        for (i, c) in line.char_indices().chain([(line.len(), '\n')]) {
+            if units == pos.col.checked_sub(1)? {
+                return Some(start + i);
+            }
+            units += match encoding {
+                Encoding::Utf8 => c.len_utf8(),
+                Encoding::Utf16 => c.len_utf16(),
+                Encoding::Utf32 => 1,
+            };
+        }
+        None
+    }
+
+    /// The line and column of the start of `span`.
+    pub fn span_start(&self, span: Span, encoding: Encoding) -> LineCol {
+        self.line_col(span.start.pos, encoding)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let index = LineIndex::new("a = é\nb = \"😀\" + c\n".into());
+        assert_eq!(index.nb_lines(), 3);
+        assert_eq!(index.line_text(1), "a = é");
+        assert_eq!(index.line_text(3), "");
+        // The `c` after the emoji.
+        let c = "a = é\nb = \"😀\" + ".len();
+        let at = |line, col| LineCol { line, col };
+        assert_eq!(index.line_col(c, Encoding::Utf8), at(2, 14));
+        assert_eq!(index.line_col(c, Encoding::Utf16), at(2, 12));
+        assert_eq!(index.line_col(c, Encoding::Utf32), at(2, 11));
+        assert_eq!(index.line_col(0, Encoding::Utf16), at(1, 1));
+        assert_eq!(index.line_col(c + 2, Encoding::Utf16), at(3, 1));
+
+        for encoding in [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32] {
+            assert_eq!(index.offset(index.line_col(c, encoding), encoding), Some(c));
+        }
+        // Inside the emoji.
+        assert_eq!(index.offset(at(2, 7), Encoding::Utf16), None);
+        assert_eq!(index.offset(at(1, 9), Encoding::Utf32), None);
+        assert_eq!(index.offset(at(4, 1), Encoding::Utf32), None);
+    }
+}
@@ -1,8 +1,8 @@
 
-use crate::lexer::token::TkKind;
-use super::parsetree::NodeKind;
+use crate::{lexer::token::{Kw, Lit, TkKind}, symbol::Symbol};
+use super::{parsetree::NodeKind, ParseError};
 
-use super::{ParserRes::*, Parser};
+use super::{state::ParserState, ParserRes::{self, *}, Parser};
 use NodeKind::*;
 use TkKind::*;
 
@@ -36,15 +36,146 @@ mk_parsers! {
         }
     };
 
+    /// `a @ T = e`, an assignment with the type of the binding.
+    pub typed_assign() = {
+        seq {
+            token(Ident) => { stack LAssign },
+            annotation(),
+            token(Equals) => { push Assign },
+            expr(),
+        }
+        then { pop }
+    };
+
+    pub annotation() = {
+        seq {
+            token(At) => { stack TypeAnnot },
+            ty(),
+        }
+        then { pop }
+    };
+
+    /// `a @ T`, the type of a binding defined elsewhere.
+    pub signature() = {
+        seq {
+            token(Ident) => { stack Signature },
+            annotation(),
+        }
+        then { pop }
+    };
+
+    /// `ListOf $ a: [a]`
+    pub type_alias() = {
+        seq {
+            token(Ident) => { stack TypeAlias },
+            token(Dollar) => { skip },
+            alias_body(),
+        }
+        then { pop }
+    };
+
+    pub alias_body() = {
+        choice {
+            type_fn(),
+            ty(),
+        }
+    };
+
+    /// A function from types to a type, `a: [a]`.
+    pub type_fn() = {
+        seq {
+            type_param() => { push TypeParam },
+            token(Colon) => { stack TypeFn },
+            alias_body(),
+        }
+        then { pop }
+    };
+
+    /// A type, function types associate to the right: `A: B: C` is `A: (B: C)`.
+    pub ty() = {
+        type_arrows(parsed)
+    };
+
+    /// A type variable, the named types are the arguments of function types.
+    pub type_param() = {
+        match parsed.symbol().filter(|_| parsed.is_kind(Ident)) {
+            Some(name) if !is_ctor(name) => Succ,
+            _ => Fail,
+        }
+    };
+
+    /// Applications of type functions, `ListOf Int`.
+    pub type_app() = {
+        type_applications(parsed)
+    };
+
+    pub type_atom() = {
+        choice {
+            type_name(),
+            array_type(),
+            paren_type(),
+        }
+    };
+
+    /// A named type, a type variable or a hole.
+    pub type_name() = {
+        name_type(parsed)
+    };
+
+    pub array_type() = {
+        seq {
+            token(Lbracket) => { stack TypeArray },
+            ty(),
+            token(Rbracket) => { skip },
+        }
+        then { pop }
+    };
+
+    pub paren_type() = {
+        seq {
+            token(Lpar) => { skip },
+            ty(),
+            token(Rpar) => { skip },
+        }
+    };
+
+    /// The top level declarations, separated by `;` or new lines.
+    pub declarations() = {
+        declaration_list(parsed)
+    };
+
+    pub declaration() = {
+        choice {
+            type_alias(),
+            binding(),
+        }
+    };
+
+    /// An expression, operators are parsed by precedence climbing.
     pub expr() = {
+        operators(parsed, 0)
+    };
+
+    /// An operand of the operators: a function, a negation or applications. The
+    /// conditionals extend as far right as possible.
+    pub prefix() = {
         choice {
-            atom(expr()),
-            fn_app(),
+            conditional(),
+            match_with(),
+            let_in(),
+            negation(),
             function(),
-            basic(TkKind::Ident, NodeKind::IdentValue),
+            fn_app(),
         }
     };
 
+    pub negation() = {
+        seq {
+            token(Dash) => { stack Neg },
+            prefix_operand(),
+        }
+        then { pop }
+    };
 
     pub atom(p: impl Parser) = {
         seq {
@@ -55,21 +186,174 @@ mk_parsers! {
         then { pop; pop }
     };
 
+    /// Applications of atoms, they bind tighter than the operators: `f a b` is `(f a) b`.
     pub fn_app() = {
+        applications(parsed)
+    };
+
+    pub fn_app_left() = { 
+        choice {
+            atom(expr()),
+            basic(TkKind::Ident, NodeKind::IdentValue),
+            literal(),
+            interpolated(),
+        }
+    };
+
+    pub literal() = {
+        match parsed.kind() {
+            Some(Litteral(_)) => {
+                parsed.push_node(Literal);
+                Succ
+            }
+            _ => Fail,
+        }
+    };
+
+    /// `"a {x} b"`, each interpolated expression is followed by the text after it.
+    pub interpolated() = {
+        interpolations(parsed)
+    };
+
+    /// `if c then a else b`, the `else` is optional and attaches to the closest `if`.
+    pub conditional() = {
+        if_then_else(parsed)
+    };
+
+    /// `match e with p -> a; q -> b;`, the last `;` is optional.
+    pub match_with() = {
+        match_arms(parsed)
+    };
+
+    pub arm() = {
         seq {
-            fn_app_left() => { stack NodeKind::FnApp },
+            pattern(),
+            token(Arrow) => { stack MatchArm },
             expr(),
         }
         then { pop }
     };
 
-    pub fn_app_left() = { 
+    /// `let a = 1; b = 2 in a + b`, the bindings are separated by `;` or new lines.
+    pub let_in() = {
+        let_bindings(parsed)
+    };
+
+    pub binding() = {
         choice {
-            atom(function()),
-            basic(TkKind::Ident, NodeKind::IdentValue),
+            typed_assign(),
+            assign(),
+            signature(),
+        }
+    };
+
+    /// A pattern: a constructor applied to its payloads or a single pattern.
+    pub pattern() = {
+        choice {
+            ctor_pattern(),
+            pattern_atom(),
         }
     };
 
+    pub ctor_pattern() = {
+        payloads(parsed)
+    };
+
+    pub pattern_atom() = {
+        choice {
+            name_pattern(),
+            literal_pattern(),
+            negative_pattern(),
+            record_pattern(),
+            array_pattern(),
+            paren_pattern(),
+        }
+    };
+
+    /// A wildcard, a variable or a constructor without payload.
+    pub name_pattern() = {
+        name(parsed)
+    };
+
+    pub literal_pattern() = {
+        match parsed.kind() {
+            Some(Litteral(_)) => {
+                parsed.push_node(PatLiteral);
+                Succ
+            }
+            _ => Fail,
+        }
+    };
+
+    /// `-1`, only numbers can be negated.
+    pub negative_pattern() = {
+        seq {
+            token(Dash) => { stack PatNeg },
+            number_pattern(),
+        }
+        then { pop }
+    };
+
+    pub number_pattern() = {
+        match parsed.kind() {
+            Some(Litteral(Lit::Int(_) | Lit::Float(_))) => {
+                parsed.push_node(PatLiteral);
+                Succ
+            }
+            _ => Fail,
+        }
+    };
+
+    /// Parentheses only group patterns, they have no node.
+    pub paren_pattern() = {
+        seq {
+            token(Lpar) => { skip },
+            pattern(),
+            token(Rpar) => { skip },
+        }
+    };
+
+    /// `{ a, b = p, ... }`
+    pub record_pattern() = {
+        seq {
+            token(Lbrace) => { push PatRecord },
+            record_fields(),
+            token(Rbrace) => { skip },
+        }
+    };
+
+    pub record_fields() = {
+        items(parsed, field_pattern(), None)
+    };
+
+    pub field_pattern() = {
+        choice {
+            field_bind(),
+            basic(Ident, PatField),
+        }
+    };
+
+    pub field_bind() = {
+        seq {
+            token(Ident) => { stack PatFieldBind },
+            token(Equals) => { skip },
+            pattern(),
+        }
+        then { pop }
+    };
+
+    /// `[a, b, ...]`
+    pub array_pattern() = {
+        seq {
+            token(Lbracket) => { push PatArray },
+            array_items(),
+            token(Rbracket) => { skip },
+        }
+    };
+
+    pub array_items() = {
+        items(parsed, pattern(), Some(PatElem))
+    };
 
     pub function() = {
         seq {
@@ -80,3 +364,382 @@ mk_parsers! {
         then { pop }
     };
 }
+
+/// The binding power of unary minus, it binds tighter than the arithmetic operators.
+const NEG_BP: u8 = 13;
+
+/// The node of a binary operator with its left and right binding powers, the side with
+/// the highest one is the one it associates to.
+fn infix(kind: TkKind) -> Option<(NodeKind, u8, u8)> {
+    let op = match kind {
+        TkKind::Pipe => (NodeKind::Pipe, 1, 2),
+        TkKind::RevPipe => (NodeKind::RevPipe, 1, 2),
+        TkKind::Fork => (NodeKind::Fork, 3, 4),
+        TkKind::Merge => (NodeKind::Merge, 5, 6),
+        LT => (Less, 7, 8),
+        GT => (Greater, 7, 8),
+        Plus => (Add, 9, 10),
+        Dash => (Sub, 9, 10),
+        Star => (Mul, 11, 12),
+        Slash => (Div, 11, 12),
+        Dot => (Compose, 16, 15),
+        DotDot => (Blackbird, 16, 15),
+        DotUnderscore => (ComposeN, 16, 15),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Parse operators binding tighter than `min_bp` and their operands. The operator node
+/// follows its operands.
+fn operators(parsed: &mut ParserState, min_bp: u8) -> ParserRes {
+    if let Fail = prefix()(parsed) {
+        return Fail;
+    }
+    // The lines of an indented operator chain, as in a multi-line pipe, continue it.
+    let mut indents = 0;
+    loop {
+        if continuation(parsed, Indent, min_bp) {
+            indents += 1;
+        } else if indents > 0 {
+            continuation(parsed, Newline, min_bp);
+        }
+        let Some((node, left, right)) = parsed.kind().and_then(infix) else {
+            break;
+        };
+        if left < min_bp {
+            break;
+        }
+        let save = parsed.save();
+        let (op, span) = (parsed.kind().unwrap(), parsed.span());
+        parsed.stack_node(node);
+        if let Fail = operators(parsed, right) {
+            // The operator is dropped from the tree.
+            parsed.restore(save);
+            parsed.skip_token();
+            parsed.push_error(ParseError::ExpectedOperand(op, span));
+            break;
+        }
+        parsed.pop_node();
+    }
+    while indents > 0 && parsed.is_kind(Dedent) {
+        parsed.skip_token();
+        indents -= 1;
+    }
+    Succ
+}
+
+/// Skip the `layout` token starting a line if the line continues an operator chain with
+/// an operator binding tighter than `min_bp`.
+fn continuation(parsed: &mut ParserState, layout: TkKind, min_bp: u8) -> bool {
+    if !parsed.is_kind(layout) {
+        return false;
+    }
+    let save = parsed.save();
+    parsed.skip_token();
+    if parsed.kind().and_then(infix).is_some_and(|(_, left, _)| left >= min_bp) {
+        return true;
+    }
+    parsed.restore(save);
+    false
+}
+
+fn interpolations(parsed: &mut ParserState) -> ParserRes {
+    if !parsed.is_kind(StrHead) {
+        return Fail;
+    }
+    parsed.push_node(Interpolated);
+    loop {
+        // An empty interpolation is reported by the lexer.
+        let _ = expr()(parsed);
+        match parsed.kind() {
+            Some(StrMiddle) => parsed.push_node(Interpolation),
+            Some(StrTail) => {
+                parsed.push_node(Interpolation);
+                return Succ;
+            }
+            _ => {
+                parsed.push_error(ParseError::UnclosedInterpolation(parsed.span()));
+                return Succ;
+            }
+        }
+    }
+}
+
+fn if_then_else(parsed: &mut ParserState) -> ParserRes {
+    let Some(if_tk) = parsed.handle().filter(|_| parsed.is_kind(KeyWord(Kw::If))) else {
+        return Fail;
+    };
+    parsed.stack_node(If);
+    if let Fail = expr()(parsed) {
+        parsed.push_error(ParseError::ExpectedBranch(Kw::If, parsed.span()));
+    }
+    // Past the `if` the conditional is committed, errors are reported instead of failing.
+    let mut indents = 0;
+    if layout_before(parsed, Kw::Then, &mut indents) {
+        branch(parsed, Then, Kw::Then);
+    } else {
+        parsed.push_error(ParseError::ExpectedThen(parsed.span(), parsed.span_of(if_tk)));
+    }
+    if layout_before(parsed, Kw::Else, &mut indents) {
+        branch(parsed, Else, Kw::Else);
+    }
+    while indents > 0 && parsed.is_kind(Dedent) {
+        parsed.skip_token();
+        indents -= 1;
+    }
+    parsed.pop_node();
+    Succ
+}
+
+/// Whether the keyword `kw` is next, skipping the layout tokens before it. `indents`
+/// counts the blocks opened by the skipped tokens.
+fn layout_before(parsed: &mut ParserState, kw: Kw, indents: &mut usize) -> bool {
+    let save = parsed.save();
+    let mut opened = *indents;
+    loop {
+        match parsed.kind() {
+            Some(Indent) => opened += 1,
+            Some(Dedent) if opened > 0 => opened -= 1,
+            Some(Newline) => {}
+            _ => break,
+        }
+        parsed.skip_token();
+    }
+    if parsed.is_kind(KeyWord(kw)) {
+        *indents = opened;
+        return true;
+    }
+    parsed.restore(save);
+    false
+}
+
+/// Parse the keyword `kw` followed by an expression into a `node`, the expression can
+/// be an indented block.
+fn branch(parsed: &mut ParserState, node: NodeKind, kw: Kw) {
+    parsed.stack_node(node);
+    let mut indents = 0;
+    while parsed.is_kind(Indent) {
+        parsed.skip_token();
+        indents += 1;
+    }
+    if let Fail = expr()(parsed) {
+        parsed.push_error(ParseError::ExpectedBranch(kw, parsed.span()));
+    }
+    while indents > 0 && parsed.is_kind(Dedent) {
+        parsed.skip_token();
+        indents -= 1;
+    }
+    parsed.pop_node();
+}
+
+fn match_arms(parsed: &mut ParserState) -> ParserRes {
+    if !parsed.is_kind(KeyWord(Kw::Match)) {
+        return Fail;
+    }
+    parsed.stack_node(Match);
+    if let Fail = expr()(parsed) {
+        parsed.push_error(ParseError::ExpectedBranch(Kw::Match, parsed.span()));
+    }
+    parsed.pop_node();
+    if !parsed.is_kind(KeyWord(Kw::With)) {
+        parsed.push_error(ParseError::ExpectedKeyword(Kw::With, parsed.span()));
+        return Succ;
+    }
+    parsed.skip_token();
+    skip_separators(parsed);
+    if let Fail = arm()(parsed) {
+        parsed.push_error(ParseError::ExpectedArm(parsed.span()));
+        return Succ;
+    }
+    loop {
+        // The separators after the last arm belong to the enclosing block.
+        let save = parsed.save();
+        skip_separators(parsed);
+        if let Fail = arm()(parsed) {
+            parsed.restore(save);
+            break;
+        }
+    }
+    Succ
+}
+
+fn let_bindings(parsed: &mut ParserState) -> ParserRes {
+    if !parsed.is_kind(KeyWord(Kw::Let)) {
+        return Fail;
+    }
+    parsed.push_node(Let);
+    skip_separators(parsed);
+    let mut bound = false;
+    while let Some(start) = parsed.handle() {
+        if let Fail = binding()(parsed) {
+            break;
+        }
+        parsed.push_node_at(LetBinding, start);
+        bound = true;
+        skip_separators(parsed);
+    }
+    if !bound {
+        parsed.push_error(ParseError::ExpectedBinding(parsed.span()));
+    }
+    if parsed.is_kind(KeyWord(Kw::In)) {
+        branch(parsed, In, Kw::In);
+    } else {
+        parsed.push_error(ParseError::ExpectedKeyword(Kw::In, parsed.span()));
+    }
+    Succ
+}
+
+fn declaration_list(parsed: &mut ParserState) -> ParserRes {
+    skip_separators(parsed);
+    while parsed.handle().is_some() {
+        if let Fail = declaration()(parsed) {
+            parsed.push_error(ParseError::ExpectedBinding(parsed.span()));
+            break;
+        }
+        skip_separators(parsed);
+    }
+    Succ
+}
+
+fn type_arrows(parsed: &mut ParserState) -> ParserRes {
+    if let Fail = type_app()(parsed) {
+        return Fail;
+    }
+    if matches!(parsed.kind(), Some(Colon | Arrow)) {
+        let save = parsed.save();
+        parsed.stack_node(TypeArrow);
+        if let Fail = ty()(parsed) {
+            // A dangling arrow is left to the caller.
+            parsed.restore(save);
+            return Succ;
+        }
+        parsed.pop_node();
+    }
+    Succ
+}
+
+fn name_type(parsed: &mut ParserState) -> ParserRes {
+    let Some(name) = parsed.symbol().filter(|_| parsed.is_kind(Ident)) else {
+        return Fail;
+    };
+    parsed.push_node(match name.as_str() {
+        "_" => TypeHole,
+        _ if is_ctor(name) => TypeName,
+        _ => TypeVar,
+    });
+    Succ
+}
+
+/// A type atom applied to the following ones, like `fn_app`. An identifier followed by
+/// `=` or `@` starts the next declaration rather than an argument, as in `a @ Int a = 2`.
+fn type_applications(parsed: &mut ParserState) -> ParserRes {
+    if let Fail = type_atom()(parsed) {
+        return Fail;
+    }
+    while let Some(arg) = parsed.handle() {
+        if starts_declaration(parsed) {
+            break;
+        }
+        if let Fail = type_atom()(parsed) {
+            break;
+        }
+        parsed.push_node_at(TypeApp, arg);
+    }
+    Succ
+}
+
+/// Whether the next tokens are a name followed by `=` or `@`.
+fn starts_declaration(parsed: &mut ParserState) -> bool {
+    if !parsed.is_kind(Ident) {
+        return false;
+    }
+    let save = parsed.save();
+    parsed.skip_token();
+    let starts = matches!(parsed.kind(), Some(Equals | At));
+    parsed.restore(save);
+    starts
+}
+
+/// Skip the `;` and the layout tokens between bindings or match arms.
+fn skip_separators(parsed: &mut ParserState) {
+    while matches!(parsed.kind(), Some(Semicolon | Indent | Dedent | Newline)) {
+        parsed.skip_token();
+    }
+}
+
+/// Whether an identifier names an enum constructor.
+fn is_ctor(name: Symbol) -> bool {
+    name.as_str().chars().next().is_some_and(char::is_uppercase)
+}
+
+fn name(parsed: &mut ParserState) -> ParserRes {
+    let Some(name) = parsed.symbol().filter(|_| parsed.is_kind(Ident)) else {
+        return Fail;
+    };
+    parsed.push_node(match name.as_str() {
+        "_" => PatWildcard,
+        _ if is_ctor(name) => PatCtor,
+        _ => PatVar,
+    });
+    Succ
+}
+
+/// A constructor followed by at least one payload.
+fn payloads(parsed: &mut ParserState) -> ParserRes {
+    if !parsed.symbol().is_some_and(|name| parsed.is_kind(Ident) && is_ctor(name)) {
+        return Fail;
+    }
+    parsed.push_node(PatCtor);
+    let mut applied = false;
+    while let Some(payload) = parsed.handle() {
+        if let Fail = pattern_atom()(parsed) {
+            break;
+        }
+        parsed.push_node_at(PatApp, payload);
+        applied = true;
+    }
+    if applied { Succ } else { Fail }
+}
+
+/// Comma separated items ended by an optional `...`, each one followed by `node` if set.
+fn items(parsed: &mut ParserState, item: impl Parser, node: Option<NodeKind>) -> ParserRes {
+    loop {
+        if parsed.is_kind(Ellipsis) {
+            parsed.stack_node(PatRest);
+            parsed.pop_node();
+            return Succ;
+        }
+        let start = parsed.handle();
+        if let Fail = item(parsed) {
+            return Succ;
+        }
+        if let (Some(node), Some(start)) = (node, start) {
+            parsed.push_node_at(node, start);
+        }
+        if !parsed.is_kind(Comma) {
+            return Succ;
+        }
+        parsed.skip_token();
+    }
+}
+
+/// The operand of a unary minus.
+fn prefix_operand() -> impl Parser {
+    |parsed: &mut ParserState| operators(parsed, NEG_BP)
+}
+
+/// Parse an atom applied to the following ones, each application node follows its
+/// argument and points to its first token.
+fn applications(parsed: &mut ParserState) -> ParserRes {
+    if let Fail = fn_app_left()(parsed) {
+        return Fail;
+    }
+    while let Some(arg) = parsed.handle() {
+        if let Fail = fn_app_left()(parsed) {
+            break;
+        }
+        parsed.push_node_at(FnApp, arg);
+    }
+    Succ
+}
@@ -27,9 +27,9 @@ macro_rules! mk_parsers {
     ) => {
         $(
             $(#[doc = $doc])*
-            $v fn $name($($param:$pty),*) -> impl crate::parser::internals::Parser {
+            $v fn $name($($param:$pty),*) -> impl $crate::parser::internals::Parser {
                 #[allow(unused_mut)]
-                move |mut $input: &mut crate::parser::internals::state::ParserState| {
+                move |mut $input: &mut $crate::parser::internals::state::ParserState| {
                     let state = $input.save();
                     mk_rule!($input, state, $rule)
                 }
@@ -144,4 +144,7 @@ macro_rules! mk_op {
     ($in: ident, pop) => {
         $in.pop_node()
     };
+    ($in: ident, skip) => {
+        $in.skip_token()
+    };
 }
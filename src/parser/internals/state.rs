@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use thiserror::Error;
 
-use crate::{lexer::{LexedBuffer, self, TkHandle, token::TkKind}, utils::IVec};
+use crate::{lexer::{LexedBuffer, self, TkHandle, token::TkKind}, utils::{IVec, Span}, diagnostic::{Diagnostic, ToDiagnostic}, symbol::Symbol};
 
 use super::Parser;
 use crate::parser::parsetree::NodeKind;
@@ -21,6 +21,16 @@ pub enum StateError
 }
 
 
+impl ToDiagnostic for StateError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            StateError::LexError(err) => err.to_diagnostic(),
+            StateError::ParseError(err) => err.to_diagnostic(),
+            _ => Diagnostic::error(self.to_string()).with_note("this is a bug of the parser"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParserState {
     input: LexedBuffer,
@@ -31,7 +41,7 @@ pub struct ParserState {
     errors: Vec<StateError>,
 }
 
-pub struct Save(usize, usize, usize);
+pub struct Save(usize, usize, usize, Option<TkHandle>);
 
 impl ParserState
 {
@@ -39,24 +49,43 @@ impl ParserState
         let kinds = Vec::with_capacity(input.nb_tokens());
         let tks = Vec::with_capacity(input.nb_tokens());
         let first = input.first();
-        Self {
+        let mut state = Self {
             input,
             kinds,
             tks,
             tk_handle: first, 
             stack: vec![],
             errors: Vec::new(),
-        }
+        };
+        state.skip_comments();
+        state
     }
 
     pub fn save(&self) -> Save {
-        Save(self.kinds.len(), self.stack.len(), self.errors.len())
+        Save(self.kinds.len(), self.stack.len(), self.errors.len(), self.tk_handle)
     }
 
-    pub fn restore(&mut self, Save(kl, sl, el): Save) {
+    pub fn restore(&mut self, Save(kl, sl, el, tk): Save) {
         self.kinds.truncate(kl);
+        self.tks.truncate(kl);
         self.stack.truncate(sl);
         self.errors.truncate(el);
+        self.tk_handle = tk;
+    }
+
+    /// The kind of the next token, `None` at the end of the input.
+    pub fn kind(&self) -> Option<TkKind> {
+        self.tk_handle.map(|h| *self.input.get_kind(h))
+    }
+
+    /// The interned name of the next token if it is an identifier or a keyword.
+    pub fn symbol(&self) -> Option<Symbol> {
+        self.tk_handle.and_then(|h| self.input.get_symbol(h))
+    }
+
+    /// The handle of the next token, `None` at the end of the input.
+    pub fn handle(&self) -> Option<TkHandle> {
+        self.tk_handle
     }
     
     pub fn is_kind(&self, kind: TkKind) -> bool {
@@ -72,12 +101,56 @@ impl ParserState
         self.kinds.push(kind);
         // It is safe to
         self.tks.push(self.tk_handle.expect("Pushed a Node kind without cheking if the token was valid."));
-        self.tk_handle = self.tk_handle.and_then(|h| self.input.next_handle(h));
+        self.advance();
     }
 
     pub fn stack_node(&mut self, kind: NodeKind) {
         self.stack.push((kind, self.tk_handle.expect("Pushed a Node kind without checking if the token was valid.")));
+        self.advance();
+    }
+
+    /// The span of the next token, or an empty one at the end of the input.
+    pub fn span(&self) -> Span {
+        let spans = self.input.spans();
+        match (self.tk_handle, spans.last()) {
+            (Some(h), _) => spans[h],
+            (None, Some(last)) => Span::new(last.file, last.end, last.end),
+            (None, None) => Span::default(),
+        }
+    }
+
+    /// The span of the token `tk`.
+    pub fn span_of(&self, tk: TkHandle) -> Span {
+        self.input.spans()[tk]
+    }
+
+    /// Record an error, the rule reporting it still succeeds so it isn't backtracked.
+    pub fn push_error(&mut self, err: crate::parser::ParseError) {
+        self.errors.push(err.into());
+    }
+
+    /// Push a node that doesn't consume a token, like an application, on the token `tk`.
+    pub fn push_node_at(&mut self, kind: NodeKind, tk: TkHandle) {
+        self.kinds.push(kind);
+        self.tks.push(tk);
+    }
+
+    /// Consume a token that only separates nodes, like `,`.
+    pub fn skip_token(&mut self) {
+        self.advance();
+    }
+
+    /// Move to the next token, the comments are skipped: the documentation is still
+    /// available from the lexed buffer.
+    fn advance(&mut self) {
         self.tk_handle = self.tk_handle.and_then(|h| self.input.next_handle(h));
+        self.skip_comments();
+    }
+
+    fn skip_comments(&mut self) {
+        while matches!(self.kind(), Some(TkKind::Comment | TkKind::DocComment)) {
+            self.tk_handle = self.tk_handle.and_then(|h| self.input.next_handle(h));
+        }
     }
 
     pub fn pop_node(&mut self) {
@@ -111,5 +184,9 @@ impl ParsedBuffer {
     pub fn nodes(&self) -> IVec<NodeKind> {
         self.nodes.clone()
     }
+
+    pub fn errors(&self) -> IVec<StateError> {
+        self.errors.clone()
+    }
 }
 
@@ -6,12 +6,64 @@ pub use internals::*;
 use thiserror::Error;
 mod grammar;
 
-
+use crate::{
+    diagnostic::{Diagnostic, ToDiagnostic},
+    lexer::token::{Kw, TkKind},
+    utils::Span,
+};
 
 #[derive(Error, Debug)]
 pub enum ParseError {
     #[error("NONE ERROR.")]
     NONE,
+    /// Where `then` was expected and the `if`.
+    #[error("Expected `then` after the condition of the `if`.")]
+    ExpectedThen(Span, Span),
+    /// The keyword and where its expression was expected.
+    #[error("Expected an expression after `{_0}`.")]
+    ExpectedBranch(Kw, Span),
+    #[error("Expected `{_0}`.")]
+    ExpectedKeyword(Kw, Span),
+    #[error("Expected a match arm `pattern -> expression`.")]
+    ExpectedArm(Span),
+    #[error("Expected a binding `name = expression`.")]
+    ExpectedBinding(Span),
+    /// The operator and its span.
+    #[error("Expected an operand after `{_0}`.")]
+    ExpectedOperand(TkKind, Span),
+    #[error("Expected `}}` to close the interpolation.")]
+    UnclosedInterpolation(Span),
+}
+
+impl ToDiagnostic for ParseError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.to_string());
+        match self {
+            ParseError::NONE => diagnostic,
+            ParseError::ExpectedThen(at, cond) => diagnostic
+                .with_code("P0001")
+                .with_label(*at, "expected `then`")
+                .with_secondary(*cond, "in this `if`"),
+            ParseError::ExpectedBranch(_, at) => diagnostic
+                .with_code("P0002")
+                .with_label(*at, "expected an expression"),
+            ParseError::ExpectedKeyword(kw, at) => diagnostic
+                .with_code("P0003")
+                .with_label(*at, format!("expected `{kw}`")),
+            ParseError::ExpectedArm(at) => diagnostic
+                .with_code("P0004")
+                .with_label(*at, "expected a pattern"),
+            ParseError::ExpectedBinding(at) => diagnostic
+                .with_code("P0005")
+                .with_label(*at, "expected a binding"),
+            ParseError::ExpectedOperand(_, op) => diagnostic
+                .with_code("P0006")
+                .with_label(*op, "this operator has no right operand"),
+            ParseError::UnclosedInterpolation(at) => diagnostic
+                .with_code("P0007")
+                .with_label(*at, "expected `}`"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -21,13 +73,11 @@ mod test {
     use super::{state::*, grammar::*, *};
     use parsetree::NodeKind::{self, *};
 
-    fn verify_nodes<'a>(pb: ParsedBuffer, expected: Vec<NodeKind>) {
-        for (&e_kind, &f_kind) in expected.iter().zip(pb.nodes().iter()) {
-            assert_eq!(e_kind, f_kind, "Expected {}, Found {}", e_kind, f_kind);
-        }
+    fn verify_nodes(pb: ParsedBuffer, expected: Vec<NodeKind>) {
+        assert_eq!(&pb.nodes()[..], &expected[..]);
     }
 
-    fn new_state<'a>(input: &str) -> ParserState {
+    fn new_state(input: &str) -> ParserState {
         let input: IStr = input.into();
         ParserState::new(Lexer::new(&input.clone()).lex().unwrap())
     }
@@ -44,15 +94,15 @@ mod test {
 
     mk_test!(test_assign, "a = x: x", assign(), [
         Assign, // = 
-        LAssign, // a
-        FnDecl, // :
         FnArg, // x
         IdentValue, // x
+        FnDecl, // :
+        LAssign, // a
     ]);
 
     mk_test!(test_fn_app, "(f: f) a", expr(), [
-        IdentValue, // f
         FnArg, // f
+        IdentValue, // f
         FnDecl, // :
         AtomEnd, //)
         AtomBegin, //(
@@ -63,19 +113,18 @@ mod test {
     ]);
 
     mk_test!(test_fn_app_2, "(f: g: f g) (x: x) a", expr(), [
-        IdentValue, // g
+        FnArg, // f
+        FnArg, // g
         IdentValue, // f
+        IdentValue, // g
         FnApp, // ?
-        
-        FnArg, // g
         FnDecl, //:
-        FnArg, // f
         FnDecl, // :
         AtomEnd, //)
         AtomBegin, //(
 
-        IdentValue, // x
         FnArg, // x
+        IdentValue, // x
         FnDecl, // :
         AtomEnd, //)
         AtomBegin, //(
@@ -87,4 +136,409 @@ mod test {
         FnApp, // ?
     ]);
 
+    mk_test!(test_precedence, "a + b * 2 - c", expr(), [
+        IdentValue, // a
+        IdentValue, // b
+        Literal, // 2
+        Mul, // *
+        Add, // +
+        IdentValue, // c
+        Sub, // -
+    ]);
+
+    mk_test!(test_app_binds_tighter, "(length point - c) - r", expr(), [
+        IdentValue, // length
+        IdentValue, // point
+        FnApp, // ?
+        IdentValue, // c
+        Sub, // -
+        AtomEnd, // )
+        AtomBegin, // (
+        IdentValue, // r
+        Sub, // -
+    ]);
+
+    mk_test!(test_unary_minus, "-x * 2 |> f . g <| -1", expr(), [
+        IdentValue, // x
+        Neg, // -
+        Literal, // 2
+        Mul, // *
+        IdentValue, // f
+        IdentValue, // g
+        Compose, // .
+        Pipe, // |>
+        Literal, // 1
+        Neg, // -
+        RevPipe, // <|
+    ]);
+
+    mk_test!(test_interpolation, r#"print "A {a + 1} and {f "{b}"}." x"#, expr(), [
+        IdentValue, // print
+        Interpolated, // "A {
+        IdentValue, // a
+        Literal, // 1
+        Add, // +
+        Interpolation, // } and {
+        IdentValue, // f
+        Interpolated, // "{
+        IdentValue, // b
+        Interpolation, // }"
+        FnApp, // "{
+        Interpolation, // }."
+        FnApp, // "A {
+        IdentValue, // x
+        FnApp, // x
+    ]);
+
+    #[test]
+    fn test_dangling_operator() {
+        let pb = new_state("a * b +").run_parser(expr());
+        assert_eq!(pb.errors()[0].to_string(), "Syntax Error: Expected an operand after `+`.");
+        verify_nodes(pb, vec![IdentValue, IdentValue, Mul]);
+    }
+
+    mk_test!(test_lambda_body, "a: b: a + b", expr(), [
+        FnArg, // a
+        FnArg, // b
+        IdentValue, // a
+        IdentValue, // b
+        Add, // +
+        FnDecl, // :
+        FnDecl, // :
+    ]);
+
+    // The `else` goes to the inner `if`.
+    mk_test!(test_dangling_else, "if a then if b then c else d", expr(), [
+        IdentValue, // a
+        IdentValue, // b
+        IdentValue, // c
+        Then, // then
+        IdentValue, // d
+        Else, // else
+        If, // if
+        Then, // then
+        If, // if
+    ]);
+
+    mk_test!(test_else_if, "if a then b else if c then d + 1 else e", expr(), [
+        IdentValue, // a
+        IdentValue, // b
+        Then, // then
+        IdentValue, // c
+        IdentValue, // d
+        Literal, // 1
+        Add, // +
+        Then, // then
+        IdentValue, // e
+        Else, // else
+        If, // if
+        Else, // else
+        If, // if
+    ]);
+
+    mk_test!(test_nested_if, "if a then (if b then c) else d", expr(), [
+        IdentValue, // a
+        IdentValue, // b
+        IdentValue, // c
+        Then, // then
+        If, // if
+        AtomEnd, // )
+        AtomBegin, // (
+        Then, // then
+        IdentValue, // d
+        Else, // else
+        If, // if
+    ]);
+
+    mk_test!(test_match, "match x with Some 0 -> a; Some n -> n + 1; _ -> b;", expr(), [
+        IdentValue, // x
+        Match, // match
+        PatCtor, // Some
+        PatLiteral, // 0
+        PatApp, // 0
+        IdentValue, // a
+        MatchArm, // ->
+        PatCtor, // Some
+        PatVar, // n
+        PatApp, // n
+        IdentValue, // n
+        Literal, // 1
+        Add, // +
+        MatchArm, // ->
+        PatWildcard, // _
+        IdentValue, // b
+        MatchArm, // ->
+    ]);
+
+    mk_test!(test_nested_patterns, "match t with Node (Leaf a) b -> a", expr(), [
+        IdentValue, // t
+        Match, // match
+        PatCtor, // Node
+        PatCtor, // Leaf
+        PatVar, // a
+        PatApp, // a
+        PatApp, // (
+        PatVar, // b
+        PatApp, // b
+        IdentValue, // a
+        MatchArm, // ->
+    ]);
+
+    mk_test!(test_record_pattern, "match p with { x, y = Some z, ... } -> z", expr(), [
+        IdentValue, // p
+        Match, // match
+        PatRecord, // {
+        PatField, // x
+        PatCtor, // Some
+        PatVar, // z
+        PatApp, // z
+        PatFieldBind, // y
+        PatRest, // ...
+        IdentValue, // z
+        MatchArm, // ->
+    ]);
+
+    mk_test!(test_array_pattern, "match l with [a, _, ...] -> a; [] -> 0", expr(), [
+        IdentValue, // l
+        Match, // match
+        PatArray, // [
+        PatVar, // a
+        PatElem, // a
+        PatWildcard, // _
+        PatElem, // _
+        PatRest, // ...
+        IdentValue, // a
+        MatchArm, // ->
+        PatArray, // [
+        Literal, // 0
+        MatchArm, // ->
+    ]);
+
+    mk_test!(test_let_in, "let a = 1; b @ Int = a in a + b", expr(), [
+        Let, // let
+        Assign, // =
+        Literal, // 1
+        LAssign, // a
+        LetBinding, // a
+        TypeName, // Int
+        TypeAnnot, // @
+        Assign, // =
+        IdentValue, // a
+        LAssign, // b
+        LetBinding, // b
+        IdentValue, // a
+        IdentValue, // b
+        Add, // +
+        In, // in
+    ]);
+
+    mk_test!(test_nested_let, "let f = let x = 1 in x in f", expr(), [
+        Let, // let
+        Assign, // =
+        Let, // let
+        Assign, // =
+        Literal, // 1
+        LAssign, // x
+        LetBinding, // x
+        IdentValue, // x
+        In, // in
+        LAssign, // f
+        LetBinding, // f
+        IdentValue, // f
+        In, // in
+    ]);
+
+    #[test]
+    fn test_let_layout() {
+        let lexed = Lexer::new("let\n    a = 2\n    b = a\nin print b").with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(expr());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            Let, Assign, Literal, LAssign, LetBinding,
+            Assign, IdentValue, LAssign, LetBinding,
+            IdentValue, IdentValue, FnApp, In,
+        ]);
+    }
+
+    mk_test!(test_typed_fn, "add_one @ Int: Int = a: a + 1", declarations(), [
+        TypeName, // Int
+        TypeName, // Int
+        TypeArrow, // :
+        TypeAnnot, // @
+        Assign, // =
+        FnArg, // a
+        IdentValue, // a
+        Literal, // 1
+        Add, // +
+        FnDecl, // :
+        LAssign, // add_one
+    ]);
+
+    mk_test!(test_type_alias, "ListOf $ a: [a]; IntList $ ListOf Int", declarations(), [
+        TypeParam, // a
+        TypeVar, // a
+        TypeArray, // [
+        TypeFn, // :
+        TypeAlias, // ListOf
+        TypeName, // ListOf
+        TypeName, // Int
+        TypeApp, // Int
+        TypeAlias, // IntList
+    ]);
+
+    mk_test!(test_type_arrows, "print_a @ a -> (Int -> Int) -> Effect _", signature(), [
+        TypeVar, // a
+        TypeName, // Int
+        TypeName, // Int
+        TypeArrow, // ->
+        TypeName, // Effect
+        TypeHole, // _
+        TypeApp, // _
+        TypeArrow, // ->
+        TypeArrow, // ->
+        TypeAnnot, // @
+        Signature, // print_a
+    ]);
+
+    #[test]
+    fn test_signature_then_definition() {
+        let lexed = Lexer::new("a @ Int\nadd_one @ Int: Int\nadd_one = a: a + 1\n").with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(declarations());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            TypeName, TypeAnnot, Signature,
+            TypeName, TypeName, TypeArrow, TypeAnnot, Signature,
+            Assign, FnArg, IdentValue, Literal, Add, FnDecl, LAssign,
+        ]);
+    }
+
+    // Without layout a signature ends before the name of the next declaration.
+    mk_test!(test_signature_then_definition_inline, "a @ Int\na = 2; b @ ListOf c\nb = a", declarations(), [
+        TypeName, // Int
+        TypeAnnot, // @
+        Signature, // a
+        Assign, // =
+        Literal, // 2
+        LAssign, // a
+        TypeName, // ListOf
+        TypeVar, // c
+        TypeApp, // c
+        TypeAnnot, // @
+        Signature, // b
+        Assign, // =
+        IdentValue, // a
+        LAssign, // b
+    ]);
+
+    mk_test!(test_named_type_arrow, "F $ Int: Bool", type_alias(), [
+        TypeName, // Int
+        TypeName, // Bool
+        TypeArrow, // :
+        TypeAlias, // F
+    ]);
+
+    #[test]
+    fn test_deep_types() {
+        let depth = 64;
+        let input = format!("{}Int{}", "[(".repeat(depth), ")]".repeat(depth));
+        let pb = new_state(&input).run_parser(ty());
+        let mut expected = vec![TypeName];
+        expected.extend([TypeArray].repeat(depth));
+        verify_nodes(pb, expected);
+    }
+
+    #[test]
+    fn test_comments_between_declarations() {
+        let input = "--| The successor.\nadd_one @ Int: Int\nadd_one = a: a + 1 -- inline\n-- aside\nb = 2\n";
+        let lexed = Lexer::new(input).with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(declarations());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            TypeName, TypeName, TypeArrow, TypeAnnot, Signature,
+            Assign, FnArg, IdentValue, Literal, Add, FnDecl, LAssign,
+            Assign, Literal, LAssign,
+        ]);
+    }
+
+    #[test]
+    fn test_pipe_layout() {
+        let input = "f = x\n    |> print\nmain = 10\n    <| div 2\n    <| print\ng = 1\n";
+        let lexed = Lexer::new(input).with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(declarations());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            Assign, IdentValue, IdentValue, Pipe, LAssign,
+            Assign, Literal, IdentValue, Literal, FnApp, RevPipe, IdentValue, RevPipe, LAssign,
+            Assign, Literal, LAssign,
+        ]);
+
+        let lexed = Lexer::new("10\n    <| div 2\n    <| print").with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(expr());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![Literal, IdentValue, Literal, FnApp, RevPipe, IdentValue, RevPipe]);
+    }
+
+    #[test]
+    fn test_let_errors() {
+        let errors = |input| {
+            let pb = new_state(input).run_parser(expr());
+            pb.errors().iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(errors("let in a"), ["Syntax Error: Expected a binding `name = expression`."]);
+        assert_eq!(errors("let a = 1 a"), ["Syntax Error: Expected `in`."]);
+        assert_eq!(errors("let a = 1 in"), ["Syntax Error: Expected an expression after `in`."]);
+    }
+
+    #[test]
+    fn test_match_layout() {
+        let input = "f = match a with\n    -1 -> x\n    B b -> b;\n    _ -> 0\ng = 1\n";
+        let lexed = Lexer::new(input).with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(declarations());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            Assign, IdentValue, Match,
+            PatLiteral, PatNeg, IdentValue, MatchArm,
+            PatCtor, PatVar, PatApp, IdentValue, MatchArm,
+            PatWildcard, Literal, MatchArm,
+            LAssign,
+            Assign, Literal, LAssign,
+        ]);
+    }
+
+    #[test]
+    fn test_match_errors() {
+        let errors = |input| {
+            let pb = new_state(input).run_parser(expr());
+            pb.errors().iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(errors("match x _ -> a"), ["Syntax Error: Expected `with`."]);
+        assert_eq!(errors("match x with -> a"), ["Syntax Error: Expected a match arm `pattern -> expression`."]);
+        assert_eq!(errors("match with _ -> a"), ["Syntax Error: Expected an expression after `match`."]);
+        assert!(errors("match x with _ -> a;").is_empty());
+    }
+
+    #[test]
+    fn test_if_layout() {
+        let input = "f = if a then\n        b\n    else\n        c\ng = if a\n    then b\n    else c\nh = 1\n";
+        let lexed = Lexer::new(input).with_layout().lex().unwrap();
+        let pb = ParserState::new(lexed).run_parser(declarations());
+        assert!(pb.errors().is_empty());
+        verify_nodes(pb, vec![
+            Assign, IdentValue, IdentValue, Then, IdentValue, Else, If, LAssign,
+            Assign, IdentValue, IdentValue, Then, IdentValue, Else, If, LAssign,
+            Assign, Literal, LAssign,
+        ]);
+    }
+
+    #[test]
+    fn test_if_errors() {
+        let errors = |input| {
+            let pb = new_state(input).run_parser(expr());
+            pb.errors().iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(errors("if a else b"), ["Syntax Error: Expected `then` after the condition of the `if`."]);
+        assert_eq!(errors("if a then b else"), ["Syntax Error: Expected an expression after `else`."]);
+        assert_eq!(errors("if then b"), ["Syntax Error: Expected an expression after `if`."]);
+        assert!(errors("if a then b").is_empty());
+    }
 }
@@ -17,6 +17,69 @@ pub enum NodeKind {
     AtomEnd,
 
     IdentValue, // a simple use of a variable.
+    Literal,    // A number, string or char.
+
+    // Interpolated strings, the nodes point to the text fragments.
+    Interpolated,  // The text before the first interpolation.
+    Interpolation, // The string so far, an expression and the text following it.
+
+    // Conditionals, the nodes follow the condition and each branch.
+    If,   // `if`, the whole conditional.
+    Then, // `then` branch.
+    Else, // `else` branch.
+
+    // Pattern matching. Nodes with any number of children are folded so that each
+    // node has a fixed number of children, like applications.
+    Match,    // `match`, its scrutinee.
+    MatchArm, // `->`, the match so far, the pattern and the expression of the arm.
+
+    // Patterns.
+    PatLiteral,   // A litteral.
+    PatNeg,       // `-`, a negative number.
+    PatVar,       // A variable binding the value.
+    PatWildcard,  // `_`.
+    PatCtor,      // An enum constructor, starting with an upper case letter.
+    PatApp,       // A constructor pattern and one of its payloads.
+    PatRecord,    // `{`, the empty record pattern.
+    PatField,     // A field bound to its name, after the record so far.
+    PatFieldBind, // `field = pattern`, after the record so far and the pattern.
+    PatArray,     // `[`, the empty array pattern.
+    PatElem,      // The array so far and an element.
+    PatRest,      // `...`, the other fields or elements of the record or array so far.
+
+    // Local bindings, the scope of the bindings spans from `Let` to `In`.
+    Let,        // `let`, the empty block of bindings.
+    LetBinding, // The bindings so far and an assignment.
+    In,         // `in`, the bindings and the body in their scope.
+
+    // Types.
+    Signature, // A binding, its type annotation.
+    TypeAnnot, // `@`, the type of the binding.
+    TypeAlias, // The name of the alias, its type after `$`.
+    TypeParam, // A parameter of a type function.
+    TypeFn,    // `:`, the parameter and the body of a type function.
+    TypeName,  // A named type, starting with an upper case letter.
+    TypeVar,   // A type variable.
+    TypeHole,  // `_`, a type left to the inference.
+    TypeApp,   // A type function and one of its arguments.
+    TypeArrow, // `:` or `->`, the type of a function from its argument to its result.
+    TypeArray, // `[`, the type of the elements.
+
+    // Operators, from the loosest to the tightest.
+    Pipe,      // `|>`
+    RevPipe,   // `<|`
+    Fork,      // `|-`
+    Merge,     // `//`
+    Less,      // `<`
+    Greater,   // `>`
+    Add,       // `+`
+    Sub,       // `-`
+    Mul,       // `*`
+    Div,       // `/`
+    Neg,       // Unary `-`
+    Compose,   // `.`
+    Blackbird, // `..`
+    ComposeN,  // `._`
 }
 
 // a: a + 1
@@ -32,6 +95,54 @@ impl Display for NodeKind {
             NodeKind::IdentValue => write!(f, "Ident as value"),
             NodeKind::Assign => write!(f, "Assign statement"),
             NodeKind::LAssign => write!(f, "Left side of the assignement"),
+            NodeKind::Interpolated => write!(f, "Interpolated string"),
+            NodeKind::Interpolation => write!(f, "Interpolation"),
+            NodeKind::Literal => write!(f, "Literal"),
+            NodeKind::If => write!(f, "If"),
+            NodeKind::Then => write!(f, "Then branch"),
+            NodeKind::Else => write!(f, "Else branch"),
+            NodeKind::Match => write!(f, "Match"),
+            NodeKind::MatchArm => write!(f, "Match arm"),
+            NodeKind::PatLiteral => write!(f, "Literal pattern"),
+            NodeKind::PatNeg => write!(f, "Negative pattern"),
+            NodeKind::PatVar => write!(f, "Variable pattern"),
+            NodeKind::PatWildcard => write!(f, "Wildcard pattern"),
+            NodeKind::PatCtor => write!(f, "Constructor pattern"),
+            NodeKind::PatApp => write!(f, "Constructor payload"),
+            NodeKind::PatRecord => write!(f, "Record pattern"),
+            NodeKind::PatField => write!(f, "Field pattern"),
+            NodeKind::PatFieldBind => write!(f, "Bound field pattern"),
+            NodeKind::PatArray => write!(f, "Array pattern"),
+            NodeKind::PatElem => write!(f, "Element pattern"),
+            NodeKind::PatRest => write!(f, "Rest pattern"),
+            NodeKind::Let => write!(f, "Let"),
+            NodeKind::LetBinding => write!(f, "Let binding"),
+            NodeKind::In => write!(f, "In"),
+            NodeKind::Signature => write!(f, "Signature"),
+            NodeKind::TypeAnnot => write!(f, "Type annotation"),
+            NodeKind::TypeAlias => write!(f, "Type alias"),
+            NodeKind::TypeParam => write!(f, "Type parameter"),
+            NodeKind::TypeFn => write!(f, "Type function"),
+            NodeKind::TypeName => write!(f, "Type name"),
+            NodeKind::TypeVar => write!(f, "Type variable"),
+            NodeKind::TypeHole => write!(f, "Type hole"),
+            NodeKind::TypeApp => write!(f, "Type application"),
+            NodeKind::TypeArrow => write!(f, "Function type"),
+            NodeKind::TypeArray => write!(f, "Array type"),
+            NodeKind::Pipe => write!(f, "Pipe"),
+            NodeKind::RevPipe => write!(f, "Reversed Pipe"),
+            NodeKind::Fork => write!(f, "Fork"),
+            NodeKind::Merge => write!(f, "Merge"),
+            NodeKind::Less => write!(f, "Less than"),
+            NodeKind::Greater => write!(f, "Greater than"),
+            NodeKind::Add => write!(f, "Addition"),
+            NodeKind::Sub => write!(f, "Subtraction"),
+            NodeKind::Mul => write!(f, "Multiplication"),
+            NodeKind::Div => write!(f, "Division"),
+            NodeKind::Neg => write!(f, "Negation"),
+            NodeKind::Compose => write!(f, "Composition"),
+            NodeKind::Blackbird => write!(f, "Blackbird"),
+            NodeKind::ComposeN => write!(f, "N-ary Composition"),
         }
     }
 }
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    lexer::{self, LexedBuffer, Lexer},
+    lines::{Encoding, LineCol, LineIndex},
+    utils::*,
+};
+
+/// The id of a file in a `SourceMap`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// A file loaded in a `SourceMap`.
+#[derive(Debug)]
+pub struct SourceFile {
+    path: PathBuf,
+    source: IStr,
+    lines: LineIndex,
+}
+
+impl SourceFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn source(&self) -> IStr {
+        self.source.clone()
+    }
+
+    pub fn lines(&self) -> &LineIndex {
+        &self.lines
+    }
+}
+
+/// Owns all the files of a program, spans refer to them through their `FileId`.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl Into<PathBuf>, source: impl Into<IStr>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        let source = source.into();
+        self.files.push(SourceFile {
+            path: path.into(),
+            lines: LineIndex::new(source.clone()),
+            source,
+        });
+        id
+    }
+
+    /// Read the file at `path` and add it.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> std::io::Result<FileId> {
+        let source = std::fs::read_to_string(path.as_ref())?;
+        Ok(self.add(path.as_ref(), source))
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files.iter().enumerate().map(|(i, f)| (FileId(i as u32), f))
+    }
+
+    /// Lex a file, its spans carry its id.
+    pub fn lex(&self, id: FileId) -> lexer::Result<LexedBuffer> {
+        Lexer::new(&self.file(id).source).in_file(id).lex()
+    }
+
+    /// The path of the file `span` is in.
+    pub fn path(&self, span: Span) -> &Path {
+        self.file(span.file).path()
+    }
+
+    /// The 1-based line and column where `span` starts.
+    pub fn line_col(&self, span: Span, encoding: Encoding) -> LineCol {
+        self.file(span.file).lines.line_col(span.start.pos, encoding)
+    }
+
+    /// The text covered by `span`.
+    pub fn snippet(&self, span: Span) -> &str {
+        let range: std::ops::Range<_> = span.into();
+        &self.file(span.file).source[range]
+    }
+
+    /// The full lines covered by `span`, without the final new line.
+    pub fn lines_of(&self, span: Span) -> &str {
+        let source = &self.file(span.file).source;
+        let start = source[..span.start.pos].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[span.end.pos..].find('\n').map_or(source.len(), |i| span.end.pos + i);
+        &source[start..end]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source_map() {
+        let mut map = SourceMap::new();
+        let main = map.add("main.sd", "main = lib.add 1 2");
+        let lib = map.add("lib.sd", "-- Library.\nadd = a: b:\n  a + b");
+        assert_ne!(main, lib);
+
+        let lexed = map.lex(lib).unwrap();
+        let plus = (0..lexed.nb_tokens()).find(|&id| *lexed.get_kind(id) == crate::lexer::token::Plus).unwrap();
+        let span = lexed.spans()[plus];
+        assert_eq!(span.file, lib);
+        assert_eq!(map.path(span), Path::new("lib.sd"));
+        assert_eq!(map.line_col(span, Encoding::Utf32), LineCol { line: 3, col: 5 });
+        assert_eq!(map.snippet(span), "+");
+        assert_eq!(map.lines_of(span), "  a + b");
+
+        let first = map.lex(main).unwrap().spans()[0];
+        assert_eq!(map.lines_of(first), "main = lib.add 1 2");
+        assert_eq!(map.files().count(), 2);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    num::NonZeroU32,
+    sync::{LazyLock, RwLock},
+};
+
+/// An interned name, two symbols are equal if and only if their names are.
+/// Symbols are shared by all the buffers lexed in the process, on any thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(NonZeroU32);
+
+/// The names are never freed, they live as long as the symbols.
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(RwLock::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        if let Some(&symbol) = INTERNER.read().unwrap().ids.get(name) {
+            return symbol;
+        }
+        let mut interner = INTERNER.write().unwrap();
+        // Another thread may have interned it since the read.
+        if let Some(&symbol) = interner.ids.get(name) {
+            return symbol;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(NonZeroU32::new(interner.names.len() as u32 + 1).unwrap());
+        interner.names.push(name);
+        interner.ids.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.read().unwrap().names[self.0.get() as usize - 1]
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let a = Symbol::intern("add");
+        assert_eq!(a, Symbol::intern(&String::from("add")));
+        assert_ne!(a, Symbol::intern("ad"));
+        assert_eq!(a.as_str(), "add");
+        assert_eq!(Symbol::intern("ad").to_string(), "ad");
+    }
+
+    #[test]
+    fn test_threads() {
+        let a = Symbol::intern("from_main");
+        let (b, c) = std::thread::spawn(move || (a.as_str(), Symbol::intern("from_thread")))
+            .join()
+            .unwrap();
+        assert_eq!(b, "from_main");
+        assert_eq!(c.as_str(), "from_thread");
+        assert_eq!(c, Symbol::intern("from_thread"));
+    }
+}